@@ -0,0 +1,39 @@
+#pragma once
+#include <cstdint>
+#include <functional>
+#include <ramu_rs_cpp.h>
+
+extern "C" {
+/// a null callback clears the callback, and drop_context is called at once
+void ramu_rs_set_callback(Memory &memory,
+                          void (*callback)(void *context, uint64_t addr,
+                                           bool is_write, uint64_t id,
+                                           uint64_t tag),
+                          void *context, void (*drop_context)(void *context));
+void ramu_rs_clear_callback(Memory &memory);
+}
+
+/// called with the addr, is_write, id and tag of a finished request by the
+/// thread ticking the memory, it should not throw
+using CompletionCallback =
+    std::function<void(uint64_t addr, bool is_write, uint64_t id, uint64_t tag)>;
+
+/// call the callback with each finished request in `tick` instead of returning
+/// it by `try_recv_addr`, an empty callback clears it
+inline void set_callback(Memory &memory, CompletionCallback callback) {
+  if (!callback) {
+    ramu_rs_clear_callback(memory);
+    return;
+  }
+  ramu_rs_set_callback(
+      memory,
+      [](void *context, uint64_t addr, bool is_write, uint64_t id,
+         uint64_t tag) {
+        (*static_cast<CompletionCallback *>(context))(addr, is_write, id, tag);
+      },
+      new CompletionCallback(std::move(callback)),
+      [](void *context) { delete static_cast<CompletionCallback *>(context); });
+}
+
+/// return the finished requests by `try_recv_addr` again
+inline void clear_callback(Memory &memory) { ramu_rs_clear_callback(memory); }
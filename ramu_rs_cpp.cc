@@ -1,10 +1,21 @@
 #include <array>
 #include <cstddef>
 #include <cstdint>
+#include <exception>
 #include <new>
 #include <string>
 #include <type_traits>
 #include <utility>
+#if __cplusplus >= 201703L
+#include <string_view>
+#endif
+
+#ifdef __GNUC__
+#pragma GCC diagnostic ignored "-Wmissing-declarations"
+#ifdef __clang__
+#pragma clang diagnostic ignored "-Wdollar-in-identifier-extension"
+#endif // __clang__
+#endif // __GNUC__
 
 namespace rust {
 inline namespace cxxbridge1 {
@@ -27,9 +38,12 @@ public:
   Str(const char *);
   Str(const char *, std::size_t);
 
-  Str &operator=(const Str &) &noexcept = default;
+  Str &operator=(const Str &) & noexcept = default;
 
   explicit operator std::string() const;
+#if __cplusplus >= 201703L
+  explicit operator std::string_view() const;
+#endif
 
   const char *data() const noexcept;
   std::size_t size() const noexcept;
@@ -81,7 +95,7 @@ public:
   explicit Box(const T &);
   explicit Box(T &&);
 
-  Box &operator=(Box &&) &noexcept;
+  Box &operator=(Box &&) & noexcept;
 
   const T *operator->() const noexcept;
   const T &operator*() const noexcept;
@@ -157,7 +171,7 @@ Box<T>::~Box() noexcept {
 }
 
 template <typename T>
-Box<T> &Box<T>::operator=(Box &&other) &noexcept {
+Box<T> &Box<T>::operator=(Box &&other) & noexcept {
   if (this->ptr) {
     this->drop();
   }
@@ -220,6 +234,27 @@ template <typename T>
 Box<T>::Box(uninit) noexcept {}
 #endif // CXXBRIDGE1_RUST_BOX
 
+#ifndef CXXBRIDGE1_RUST_ERROR
+#define CXXBRIDGE1_RUST_ERROR
+class Error final : public std::exception {
+public:
+  Error(const Error &);
+  Error(Error &&) noexcept;
+  ~Error() noexcept override;
+
+  Error &operator=(const Error &) &;
+  Error &operator=(Error &&) & noexcept;
+
+  const char *what() const noexcept override;
+
+private:
+  Error() noexcept = default;
+  friend impl<Error>;
+  const char *msg;
+  std::size_t len;
+};
+#endif // CXXBRIDGE1_RUST_ERROR
+
 #ifndef CXXBRIDGE1_RUST_OPAQUE
 #define CXXBRIDGE1_RUST_OPAQUE
 class Opaque {
@@ -297,19 +332,75 @@ std::size_t align_of() {
   return layout::align_of<T>();
 }
 #endif // CXXBRIDGE1_LAYOUT
+
+namespace repr {
+struct PtrLen final {
+  void *ptr;
+  ::std::size_t len;
+};
+} // namespace repr
+
+namespace detail {
+template <typename T, typename = void *>
+struct operator_new {
+  void *operator()(::std::size_t sz) { return ::operator new(sz); }
+};
+
+template <typename T>
+struct operator_new<T, decltype(T::operator new(sizeof(T)))> {
+  void *operator()(::std::size_t sz) { return T::operator new(sz); }
+};
+} // namespace detail
+
+template <typename T>
+union MaybeUninit {
+  T value;
+  void *operator new(::std::size_t sz) { return detail::operator_new<T>{}(sz); }
+  MaybeUninit() {}
+  ~MaybeUninit() {}
+};
+
+namespace {
+template <>
+class impl<Error> final {
+public:
+  static Error error(repr::PtrLen repr) noexcept {
+    Error error;
+    error.msg = static_cast<char const *>(repr.ptr);
+    error.len = repr.len;
+    return error;
+  }
+};
+} // namespace
 } // namespace cxxbridge1
 } // namespace rust
 
-struct SimpleDDR4;
+struct Memory;
+struct Generator;
 
-#ifndef CXXBRIDGE1_STRUCT_SimpleDDR4
-#define CXXBRIDGE1_STRUCT_SimpleDDR4
-struct SimpleDDR4 final : public ::rust::Opaque {
-  void tick_ddr4() noexcept;
-  bool try_send_addr(::std::uint64_t addr, bool is_write) noexcept;
-  bool try_recv_addr(::std::uint64_t &addr, bool &is_write) noexcept;
+#ifndef CXXBRIDGE1_STRUCT_Memory
+#define CXXBRIDGE1_STRUCT_Memory
+struct Memory final : public ::rust::Opaque {
+  void tick() noexcept;
+  bool try_send_addr(::std::uint64_t addr, bool is_write, ::std::uint64_t id, ::std::uint64_t tag);
+  bool try_recv_addr(::std::uint64_t &addr, bool &is_write, ::std::uint64_t &id, ::std::uint64_t &tag) noexcept;
   ::std::uint64_t get_cycle() const noexcept;
-  ~SimpleDDR4() = delete;
+  ~Memory() = delete;
+
+private:
+  friend ::rust::layout;
+  struct layout {
+    static ::std::size_t size() noexcept;
+    static ::std::size_t align() noexcept;
+  };
+};
+#endif // CXXBRIDGE1_STRUCT_Memory
+
+#ifndef CXXBRIDGE1_STRUCT_Generator
+#define CXXBRIDGE1_STRUCT_Generator
+struct Generator final : public ::rust::Opaque {
+  bool try_next_addr(::std::uint64_t &addr, bool &is_write) noexcept;
+  ~Generator() = delete;
 
 private:
   friend ::rust::layout;
@@ -318,76 +409,129 @@ private:
     static ::std::size_t align() noexcept;
   };
 };
-#endif // CXXBRIDGE1_STRUCT_SimpleDDR4
+#endif // CXXBRIDGE1_STRUCT_Generator
 
 extern "C" {
-::std::size_t cxxbridge1$SimpleDDR4$operator$sizeof() noexcept;
-::std::size_t cxxbridge1$SimpleDDR4$operator$alignof() noexcept;
+::std::size_t cxxbridge1$205$Memory$operator$sizeof() noexcept;
+::std::size_t cxxbridge1$205$Memory$operator$alignof() noexcept;
+
+void cxxbridge1$205$init_logger() noexcept;
 
-void cxxbridge1$init_logger() noexcept;
+::rust::repr::PtrLen cxxbridge1$205$new_memory(::rust::Str config, ::rust::Box<::Memory> *return$) noexcept;
 
-::SimpleDDR4 *cxxbridge1$new_ddr4(::rust::Str config) noexcept;
+void cxxbridge1$205$Memory$tick(::Memory &self) noexcept;
 
-void cxxbridge1$SimpleDDR4$tick_ddr4(::SimpleDDR4 &self) noexcept;
+::rust::repr::PtrLen cxxbridge1$205$Memory$try_send_addr(::Memory &self, ::std::uint64_t addr, bool is_write, ::std::uint64_t id, ::std::uint64_t tag, bool *return$) noexcept;
 
-bool cxxbridge1$SimpleDDR4$try_send_addr(::SimpleDDR4 &self, ::std::uint64_t addr, bool is_write) noexcept;
+bool cxxbridge1$205$Memory$try_recv_addr(::Memory &self, ::std::uint64_t &addr, bool &is_write, ::std::uint64_t &id, ::std::uint64_t &tag) noexcept;
 
-bool cxxbridge1$SimpleDDR4$try_recv_addr(::SimpleDDR4 &self, ::std::uint64_t &addr, bool &is_write) noexcept;
+::std::uint64_t cxxbridge1$205$Memory$get_cycle(::Memory const &self) noexcept;
+::std::size_t cxxbridge1$205$Generator$operator$sizeof() noexcept;
+::std::size_t cxxbridge1$205$Generator$operator$alignof() noexcept;
 
-::std::uint64_t cxxbridge1$SimpleDDR4$get_cycle(const ::SimpleDDR4 &self) noexcept;
+::rust::repr::PtrLen cxxbridge1$205$new_generator(::rust::Str config, ::Memory const &memory, ::rust::Box<::Generator> *return$) noexcept;
+
+bool cxxbridge1$205$Generator$try_next_addr(::Generator &self, ::std::uint64_t &addr, bool &is_write) noexcept;
 } // extern "C"
 
-::std::size_t SimpleDDR4::layout::size() noexcept {
-  return cxxbridge1$SimpleDDR4$operator$sizeof();
+::std::size_t Memory::layout::size() noexcept {
+  return cxxbridge1$205$Memory$operator$sizeof();
 }
 
-::std::size_t SimpleDDR4::layout::align() noexcept {
-  return cxxbridge1$SimpleDDR4$operator$alignof();
+::std::size_t Memory::layout::align() noexcept {
+  return cxxbridge1$205$Memory$operator$alignof();
 }
 
 void init_logger() noexcept {
-  cxxbridge1$init_logger();
+  cxxbridge1$205$init_logger();
+}
+
+::rust::Box<::Memory> new_memory(::rust::Str config) {
+  ::rust::MaybeUninit<::rust::Box<::Memory>> return$;
+  ::rust::repr::PtrLen error$ = cxxbridge1$205$new_memory(config, &return$.value);
+  if (error$.ptr) {
+    throw ::rust::impl<::rust::Error>::error(error$);
+  }
+  return ::std::move(return$.value);
+}
+
+void Memory::tick() noexcept {
+  cxxbridge1$205$Memory$tick(*this);
+}
+
+bool Memory::try_send_addr(::std::uint64_t addr, bool is_write, ::std::uint64_t id, ::std::uint64_t tag) {
+  ::rust::MaybeUninit<bool> return$;
+  ::rust::repr::PtrLen error$ = cxxbridge1$205$Memory$try_send_addr(*this, addr, is_write, id, tag, &return$.value);
+  if (error$.ptr) {
+    throw ::rust::impl<::rust::Error>::error(error$);
+  }
+  return ::std::move(return$.value);
 }
 
-::rust::Box<::SimpleDDR4> new_ddr4(::rust::Str config) noexcept {
-  return ::rust::Box<::SimpleDDR4>::from_raw(cxxbridge1$new_ddr4(config));
+bool Memory::try_recv_addr(::std::uint64_t &addr, bool &is_write, ::std::uint64_t &id, ::std::uint64_t &tag) noexcept {
+  return cxxbridge1$205$Memory$try_recv_addr(*this, addr, is_write, id, tag);
 }
 
-void SimpleDDR4::tick_ddr4() noexcept {
-  cxxbridge1$SimpleDDR4$tick_ddr4(*this);
+::std::uint64_t Memory::get_cycle() const noexcept {
+  return cxxbridge1$205$Memory$get_cycle(*this);
 }
 
-bool SimpleDDR4::try_send_addr(::std::uint64_t addr, bool is_write) noexcept {
-  return cxxbridge1$SimpleDDR4$try_send_addr(*this, addr, is_write);
+::std::size_t Generator::layout::size() noexcept {
+  return cxxbridge1$205$Generator$operator$sizeof();
 }
 
-bool SimpleDDR4::try_recv_addr(::std::uint64_t &addr, bool &is_write) noexcept {
-  return cxxbridge1$SimpleDDR4$try_recv_addr(*this, addr, is_write);
+::std::size_t Generator::layout::align() noexcept {
+  return cxxbridge1$205$Generator$operator$alignof();
+}
+
+::rust::Box<::Generator> new_generator(::rust::Str config, ::Memory const &memory) {
+  ::rust::MaybeUninit<::rust::Box<::Generator>> return$;
+  ::rust::repr::PtrLen error$ = cxxbridge1$205$new_generator(config, memory, &return$.value);
+  if (error$.ptr) {
+    throw ::rust::impl<::rust::Error>::error(error$);
+  }
+  return ::std::move(return$.value);
 }
 
-::std::uint64_t SimpleDDR4::get_cycle() const noexcept {
-  return cxxbridge1$SimpleDDR4$get_cycle(*this);
+bool Generator::try_next_addr(::std::uint64_t &addr, bool &is_write) noexcept {
+  return cxxbridge1$205$Generator$try_next_addr(*this, addr, is_write);
 }
 
 extern "C" {
-::SimpleDDR4 *cxxbridge1$box$SimpleDDR4$alloc() noexcept;
-void cxxbridge1$box$SimpleDDR4$dealloc(::SimpleDDR4 *) noexcept;
-void cxxbridge1$box$SimpleDDR4$drop(::rust::Box<::SimpleDDR4> *ptr) noexcept;
+::Memory *cxxbridge1$box$Memory$alloc() noexcept;
+void cxxbridge1$box$Memory$dealloc(::Memory *) noexcept;
+void cxxbridge1$box$Memory$drop(::rust::Box<::Memory> *ptr) noexcept;
+
+::Generator *cxxbridge1$box$Generator$alloc() noexcept;
+void cxxbridge1$box$Generator$dealloc(::Generator *) noexcept;
+void cxxbridge1$box$Generator$drop(::rust::Box<::Generator> *ptr) noexcept;
 } // extern "C"
 
 namespace rust {
 inline namespace cxxbridge1 {
 template <>
-::SimpleDDR4 *Box<::SimpleDDR4>::allocation::alloc() noexcept {
-  return cxxbridge1$box$SimpleDDR4$alloc();
+::Memory *Box<::Memory>::allocation::alloc() noexcept {
+  return cxxbridge1$box$Memory$alloc();
+}
+template <>
+void Box<::Memory>::allocation::dealloc(::Memory *ptr) noexcept {
+  cxxbridge1$box$Memory$dealloc(ptr);
+}
+template <>
+void Box<::Memory>::drop() noexcept {
+  cxxbridge1$box$Memory$drop(this);
+}
+template <>
+::Generator *Box<::Generator>::allocation::alloc() noexcept {
+  return cxxbridge1$box$Generator$alloc();
 }
 template <>
-void Box<::SimpleDDR4>::allocation::dealloc(::SimpleDDR4 *ptr) noexcept {
-  cxxbridge1$box$SimpleDDR4$dealloc(ptr);
+void Box<::Generator>::allocation::dealloc(::Generator *ptr) noexcept {
+  cxxbridge1$box$Generator$dealloc(ptr);
 }
 template <>
-void Box<::SimpleDDR4>::drop() noexcept {
-  cxxbridge1$box$SimpleDDR4$drop(this);
+void Box<::Generator>::drop() noexcept {
+  cxxbridge1$box$Generator$drop(this);
 }
 } // namespace cxxbridge1
 } // namespace rust
@@ -6,6 +6,14 @@
 #include <string>
 #include <type_traits>
 #include <utility>
+#if __cplusplus >= 201703L
+#include <string_view>
+#endif
+
+#ifdef __clang__
+#pragma clang diagnostic push
+#pragma clang diagnostic ignored "-Wdollar-in-identifier-extension"
+#endif // __clang__
 
 namespace rust {
 inline namespace cxxbridge1 {
@@ -28,9 +36,12 @@ public:
   Str(const char *);
   Str(const char *, std::size_t);
 
-  Str &operator=(const Str &) &noexcept = default;
+  Str &operator=(const Str &) & noexcept = default;
 
   explicit operator std::string() const;
+#if __cplusplus >= 201703L
+  explicit operator std::string_view() const;
+#endif
 
   const char *data() const noexcept;
   std::size_t size() const noexcept;
@@ -82,7 +93,7 @@ public:
   explicit Box(const T &);
   explicit Box(T &&);
 
-  Box &operator=(Box &&) &noexcept;
+  Box &operator=(Box &&) & noexcept;
 
   const T *operator->() const noexcept;
   const T &operator*() const noexcept;
@@ -158,7 +169,7 @@ Box<T>::~Box() noexcept {
 }
 
 template <typename T>
-Box<T> &Box<T>::operator=(Box &&other) &noexcept {
+Box<T> &Box<T>::operator=(Box &&other) & noexcept {
   if (this->ptr) {
     this->drop();
   }
@@ -301,16 +312,32 @@ std::size_t align_of() {
 } // namespace cxxbridge1
 } // namespace rust
 
-struct SimpleDDR4;
+struct Memory;
+struct Generator;
 
-#ifndef CXXBRIDGE1_STRUCT_SimpleDDR4
-#define CXXBRIDGE1_STRUCT_SimpleDDR4
-struct SimpleDDR4 final : public ::rust::Opaque {
-  void tick_ddr4() noexcept;
-  bool try_send_addr(::std::uint64_t addr, bool is_write) noexcept;
-  bool try_recv_addr(::std::uint64_t &addr, bool &is_write) noexcept;
+#ifndef CXXBRIDGE1_STRUCT_Memory
+#define CXXBRIDGE1_STRUCT_Memory
+struct Memory final : public ::rust::Opaque {
+  void tick() noexcept;
+  bool try_send_addr(::std::uint64_t addr, bool is_write, ::std::uint64_t id, ::std::uint64_t tag);
+  bool try_recv_addr(::std::uint64_t &addr, bool &is_write, ::std::uint64_t &id, ::std::uint64_t &tag) noexcept;
   ::std::uint64_t get_cycle() const noexcept;
-  ~SimpleDDR4() = delete;
+  ~Memory() = delete;
+
+private:
+  friend ::rust::layout;
+  struct layout {
+    static ::std::size_t size() noexcept;
+    static ::std::size_t align() noexcept;
+  };
+};
+#endif // CXXBRIDGE1_STRUCT_Memory
+
+#ifndef CXXBRIDGE1_STRUCT_Generator
+#define CXXBRIDGE1_STRUCT_Generator
+struct Generator final : public ::rust::Opaque {
+  bool try_next_addr(::std::uint64_t &addr, bool &is_write) noexcept;
+  ~Generator() = delete;
 
 private:
   friend ::rust::layout;
@@ -319,8 +346,14 @@ private:
     static ::std::size_t align() noexcept;
   };
 };
-#endif // CXXBRIDGE1_STRUCT_SimpleDDR4
+#endif // CXXBRIDGE1_STRUCT_Generator
 
 void init_logger() noexcept;
 
-::rust::Box<::SimpleDDR4> new_ddr4(::rust::Str config) noexcept;
+::rust::Box<::Memory> new_memory(::rust::Str config);
+
+::rust::Box<::Generator> new_generator(::rust::Str config, ::Memory const &memory);
+
+#ifdef __clang__
+#pragma clang diagnostic pop
+#endif // __clang__
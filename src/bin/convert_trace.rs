@@ -0,0 +1,64 @@
+//! convert a ramulator text trace to the binary trace
+//!
+//! usage: `convert_trace <memory|cpu|timed|dramsim3|champsim> <input> <output>`
+//! - the input can be compressed by gzip, zstd or xz
+//! - the output is compressed if it ends with `.gz` or `.zst`
+
+use ramu_rs::{
+    trace::{
+        champsim_records, convert_to_binary, cpu_trace_records, create_trace, BinaryTraceWriter,
+        ChampSimTraceReader, CpuTraceReader, DramSim3TraceReader, MemoryTraceReader,
+        TimedTraceReader, TraceRecord,
+    },
+    Error,
+};
+
+fn run(format: &str, input: &str, output: &str) -> Result<u64, Error> {
+    let mut writer = BinaryTraceWriter::new(
+        create_trace(output)?,
+        matches!(format, "timed" | "dramsim3"),
+    )?;
+    let count = match format {
+        "memory" => convert_to_binary(
+            MemoryTraceReader::from_path(input)?
+                .map(|req| req.map(|req| TraceRecord::from_request(&req))),
+            &mut writer,
+        )?,
+        "cpu" => convert_to_binary(
+            cpu_trace_records(CpuTraceReader::from_path(input)?),
+            &mut writer,
+        )?,
+        "timed" => convert_to_binary(TimedTraceReader::from_path(input)?, &mut writer)?,
+        "dramsim3" => convert_to_binary(DramSim3TraceReader::from_path(input)?, &mut writer)?,
+        "champsim" => convert_to_binary(
+            champsim_records(ChampSimTraceReader::from_path(input)?),
+            &mut writer,
+        )?,
+        _ => unreachable!(),
+    };
+    writer.finish()?;
+    Ok(count)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4
+        || !matches!(
+            args[1].as_str(),
+            "memory" | "cpu" | "timed" | "dramsim3" | "champsim"
+        )
+    {
+        eprintln!(
+            "usage: {} <memory|cpu|timed|dramsim3|champsim> <input> <output>",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    match run(&args[1], &args[2], &args[3]) {
+        Ok(count) => println!("converted {} records to {}", count, args[3]),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! run a synthetic traffic generator on the memory, or write its requests to a binary trace
+//!
+//! usage: `generate_traffic <memory config> <generator config> [output trace]`
+
+use ramu_rs::{
+    config::Config,
+    generator::{Generator, GeneratorConfig},
+    memory::AnyMemory,
+    replay::TimestampUnit,
+    trace::{create_trace, BinaryTraceWriter, TraceRecord},
+    Error,
+};
+
+fn run(memory_config: &str, generator_config: &str, output: Option<&str>) -> Result<(), Error> {
+    let mut memory = AnyMemory::new(Config::from_toml_path(memory_config)?)?;
+    let generator = Generator::new(GeneratorConfig::from_toml_path(generator_config)?, &memory)?;
+    match output {
+        Some(output) => {
+            let mut writer = BinaryTraceWriter::new(create_trace(output)?, false)?;
+            let count = generator.write_trace(&mut writer)?;
+            writer.finish()?;
+            println!("generated {} requests to {}", count, output);
+        }
+        None => {
+            let stats = memory.replay(
+                generator.map(|req| Ok(TraceRecord::from_request(&req))),
+                TimestampUnit::Cycle,
+            )?;
+            println!("requests: {}", stats.queueing_delays.len());
+            println!("cycles: {}", stats.cycles);
+            println!(
+                "queueing delay: average {:.2}, max {}",
+                stats.average_queueing_delay(),
+                stats.max_queueing_delay()
+            );
+            println!("{:?}", memory.stats());
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if !(3..=4).contains(&args.len()) {
+        eprintln!(
+            "usage: {} <memory config> <generator config> [output trace]",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    if let Err(e) = run(&args[1], &args[2], args.get(3).map(String::as_str)) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,65 @@
+//! run a core on a cpu trace and report the ipc
+//!
+//! usage: `run_cpu_trace <memory config> <cpu trace> [core config] [cache config]`
+
+use ramu_rs::{
+    cache::CacheConfig,
+    config::Config,
+    cpu::{Core, CoreConfig},
+    memory::AnyMemory,
+    trace::CpuTraceReader,
+    Error,
+};
+
+fn run(
+    memory_config: &str,
+    trace: &str,
+    core_config: Option<&str>,
+    cache_config: Option<&str>,
+) -> Result<(), Error> {
+    let mut memory = AnyMemory::new(Config::from_toml_path(memory_config)?)?;
+    let core_config = match core_config {
+        Some(path) => CoreConfig::from_toml_path(path)?,
+        None => Default::default(),
+    };
+    let mut core = Core::new(core_config, CpuTraceReader::from_path(trace)?);
+    let stats = match cache_config {
+        Some(path) => {
+            let (stats, cache_stats) =
+                memory.run_core_with_cache(&mut core, CacheConfig::from_toml_path(path)?)?;
+            println!(
+                "cache hits: {}, misses: {}, mshr hits: {}, writebacks: {}",
+                cache_stats.hits, cache_stats.misses, cache_stats.mshr_hits, cache_stats.writebacks
+            );
+            stats
+        }
+        None => memory.run_core(&mut core)?,
+    };
+    println!("cpu cycles: {}", stats.cycles);
+    println!("instructions: {}", stats.instructions);
+    println!("ipc: {:.4}", stats.ipc());
+    println!("reads: {}, writebacks: {}", stats.reads, stats.writebacks);
+    println!("window full cycles: {}", stats.window_full_cycles);
+    println!("memory cycles: {}", memory.get_cycle());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if !(3..=5).contains(&args.len()) {
+        eprintln!(
+            "usage: {} <memory config> <cpu trace> [core config] [cache config]",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    if let Err(e) = run(
+        &args[1],
+        &args[2],
+        args.get(3).map(String::as_str),
+        args.get(4).map(String::as_str),
+    ) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
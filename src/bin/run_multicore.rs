@@ -0,0 +1,62 @@
+//! run several cpu traces as cores sharing one memory, and report the statistics of each core
+//!
+//! usage: `run_multicore <memory config> <core config> <cpu trace>...`
+
+use ramu_rs::{
+    config::Config,
+    cpu::{Core, CoreConfig},
+    memory::AnyMemory,
+    trace::CpuTraceReader,
+    Error,
+};
+
+fn run(memory_config: &str, core_config: &str, traces: &[String]) -> Result<(), Error> {
+    let mut memory = AnyMemory::new(Config::from_toml_path(memory_config)?)?;
+    let core_config = CoreConfig::from_toml_path(core_config)?;
+    let mut cores = traces
+        .iter()
+        .map(|trace| {
+            Ok(Core::new(
+                core_config.clone(),
+                CpuTraceReader::from_path(trace)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let stats = memory.run_cores(&mut cores)?;
+    let cycles = memory.get_cycle();
+    let clk_ns = memory.clk_ns();
+    let sources = memory.source_stats();
+    for (id, (trace, stats)) in traces.iter().zip(stats).enumerate() {
+        println!("core {}: {}", id, trace);
+        println!("  cpu cycles: {}", stats.cycles);
+        println!("  instructions: {}", stats.instructions);
+        println!("  ipc: {:.4}", stats.ipc());
+        if let Some(source) = sources.get(id) {
+            println!("  memory requests: {}", source.requests());
+            println!("  average latency: {:.2}", source.average_latency());
+            println!("  max latency: {}", source.max_latency);
+            println!(
+                "  bandwidth: {:.4} GB/s",
+                source.bandwidth_gbps(cycles, clk_ns)
+            );
+            println!("  row hit rate: {:.4}", source.row_hit_rate());
+        }
+    }
+    println!("memory cycles: {}", cycles);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "usage: {} <memory config> <core config> <cpu trace>...",
+            args[0]
+        );
+        std::process::exit(2);
+    }
+    if let Err(e) = run(&args[1], &args[2], &args[3..]) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,451 @@
+//! a set-associative last level cache in front of the memory
+//! - write-back and write-allocate, a dirty victim is written back to the memory
+//! - a miss takes a mshr entry, the later misses to the same line wait in it
+//! - the cache is ticked with the memory, so the hit latency is in memory cycles
+
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, RngExt, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    memory::{MemoryTrait, SendError},
+    request::{ReqType, Request},
+};
+
+/// which line to evict in a full set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Replacement {
+    /// the least recently used line
+    #[default]
+    Lru,
+    /// a random line
+    Random,
+}
+
+/// the config of the cache
+/// ## Example
+/// ```toml
+/// size = 2097152
+/// ways = 16
+/// line_size = 64
+/// replacement = "Lru"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// the bytes of the cache
+    pub size: u64,
+    pub ways: usize,
+    pub line_size: u64,
+    pub replacement: Replacement,
+    /// the memory cycles to return a hit
+    pub hit_latency: u64,
+    /// the misses to different lines that can be outstanding
+    pub mshr_entries: usize,
+    /// the seed of the random replacement
+    pub seed: u64,
+}
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            size: 2 << 20,
+            ways: 16,
+            line_size: 64,
+            replacement: Default::default(),
+            hit_latency: 10,
+            mshr_entries: 32,
+            seed: 0,
+        }
+    }
+}
+impl CacheConfig {
+    pub fn from_toml_path(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_toml_str(&content)
+    }
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+    /// the number of sets
+    pub fn sets(&self) -> u64 {
+        self.size / self.line_size / self.ways as u64
+    }
+}
+
+/// the statistics of the cache
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// the misses waiting for an outstanding miss to the same line
+    pub mshr_hits: u64,
+    /// the dirty lines evicted and written back to the memory
+    pub writebacks: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    tag: u64,
+    dirty: bool,
+    last_used: u64,
+    /// the source of the last write, or of the fetch, the writeback is sent for it
+    owner: usize,
+}
+
+/// an outstanding miss, the requests wait for the line
+#[derive(Debug)]
+struct Mshr {
+    line: u64,
+    waiting: Vec<Request>,
+}
+
+/// the cache in front of a memory, itself a memory to the cpu
+pub struct Cache<M> {
+    config: CacheConfig,
+    memory: M,
+    sets: Vec<Vec<Line>>,
+    mshrs: Vec<Mshr>,
+    /// the hits waiting for the hit latency, with the cycle to return
+    hit_queue: VecDeque<(u64, Request)>,
+    /// the writebacks not accepted by the memory yet
+    writeback_queue: VecDeque<Request>,
+    ret_queue: VecDeque<Request>,
+    /// the counter of the accesses, for the lru replacement
+    accesses: u64,
+    rng: StdRng,
+    pub stats: CacheStats,
+}
+
+impl<M: MemoryTrait> Cache<M> {
+    pub fn new(config: CacheConfig, memory: M) -> Result<Self, Error> {
+        if memory.has_callback() {
+            return Err(Error::CallbackSet);
+        }
+        if !config.line_size.is_power_of_two() || config.ways == 0 {
+            return Err(Error::InvalidCache(format!(
+                "the line_size {} should be a power of two and the ways {} should not be 0",
+                config.line_size, config.ways
+            )));
+        }
+        let sets = config.sets();
+        if !sets.is_power_of_two() || sets * config.ways as u64 * config.line_size != config.size {
+            return Err(Error::InvalidCache(format!(
+                "the size {} should be a power of two sets of {} ways of {} bytes",
+                config.size, config.ways, config.line_size
+            )));
+        }
+        if config.mshr_entries == 0 {
+            return Err(Error::InvalidCache(
+                "the mshr_entries should not be 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            sets: vec![Vec::with_capacity(config.ways); sets as usize],
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            memory,
+            mshrs: vec![],
+            hit_queue: Default::default(),
+            writeback_queue: Default::default(),
+            ret_queue: Default::default(),
+            accesses: 0,
+            stats: Default::default(),
+        })
+    }
+
+    /// the memory behind the cache
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    fn set_and_tag(&self, line: u64) -> (usize, u64) {
+        let sets = self.sets.len() as u64;
+        ((line % sets) as usize, line / sets)
+    }
+
+    /// fill the line returned by the memory, evict a line if the set is full
+    fn fill(&mut self, line: u64, dirty: bool, owner: usize) {
+        let (set_index, tag) = self.set_and_tag(line);
+        self.accesses += 1;
+        let new_line = Line {
+            tag,
+            dirty,
+            last_used: self.accesses,
+            owner,
+        };
+        let set = &mut self.sets[set_index];
+        if set.len() < self.config.ways {
+            set.push(new_line);
+            return;
+        }
+        let victim = match self.config.replacement {
+            Replacement::Lru => (0..set.len()).min_by_key(|&i| set[i].last_used).unwrap(),
+            Replacement::Random => self.rng.random_range(0..set.len()),
+        };
+        let victim = std::mem::replace(&mut set[victim], new_line);
+        if victim.dirty {
+            let sets = self.sets.len() as u64;
+            let addr = (victim.tag * sets + set_index as u64) * self.config.line_size;
+            self.stats.writebacks += 1;
+            self.writeback_queue
+                .push_back(Request::new_write(addr).with_source(victim.owner));
+        }
+    }
+}
+
+impl<M: MemoryTrait> MemoryTrait for Cache<M> {
+    type T = M::T;
+
+    fn clk_ns(&self) -> f64 {
+        self.memory.clk_ns()
+    }
+
+    fn get_cycle(&self) -> u64 {
+        self.memory.get_cycle()
+    }
+
+    fn tick(&mut self) {
+        self.memory.tick();
+        let clk = self.memory.get_cycle();
+        while let Some(req) = self.memory.try_recv() {
+            if req.req_type.is_write() {
+                // the writebacks need no response
+                continue;
+            }
+            let line = req.addr / self.config.line_size;
+            if let Some(index) = self.mshrs.iter().position(|mshr| mshr.line == line) {
+                let mshr = self.mshrs.swap_remove(index);
+                let write = mshr.waiting.iter().rfind(|req| req.req_type.is_write());
+                let owner = write.unwrap_or(&mshr.waiting[0]).source_id;
+                self.fill(line, write.is_some(), owner);
+                for mut req in mshr.waiting {
+                    req.finish_time = clk;
+                    self.ret_queue.push_back(req);
+                }
+            }
+        }
+        while let Some(writeback) = self.writeback_queue.pop_front() {
+            if let Err(e) = self.memory.try_send(writeback) {
+                self.writeback_queue.push_front(e.into_request());
+                break;
+            }
+        }
+        while self
+            .hit_queue
+            .front()
+            .is_some_and(|(ready, _)| *ready <= clk)
+        {
+            let (_, mut req) = self.hit_queue.pop_front().unwrap();
+            req.finish_time = clk;
+            self.ret_queue.push_back(req);
+        }
+    }
+
+    fn try_send(&mut self, req: Request) -> Result<(), SendError> {
+        let line = req.addr / self.config.line_size;
+        let (set_index, tag) = self.set_and_tag(line);
+        self.accesses += 1;
+        let accesses = self.accesses;
+        if let Some(hit) = self.sets[set_index].iter_mut().find(|l| l.tag == tag) {
+            hit.last_used = accesses;
+            if req.req_type.is_write() {
+                hit.dirty = true;
+                hit.owner = req.source_id;
+            }
+            self.stats.hits += 1;
+            let ready = self.memory.get_cycle() + self.config.hit_latency;
+            self.hit_queue.push_back((ready, req));
+            return Ok(());
+        }
+        if let Some(mshr) = self.mshrs.iter_mut().find(|mshr| mshr.line == line) {
+            self.stats.mshr_hits += 1;
+            mshr.waiting.push(req);
+            return Ok(());
+        }
+        if self.mshrs.len() >= self.config.mshr_entries {
+            return Err(SendError::Full(req));
+        }
+        // fetch the whole line, a write miss also fetches the line
+        let fetch =
+            Request::new(line * self.config.line_size, ReqType::Read).with_source(req.source_id);
+        match self.memory.try_send(fetch) {
+            Ok(()) => {
+                self.stats.misses += 1;
+                self.mshrs.push(Mshr {
+                    line,
+                    waiting: vec![req],
+                });
+                Ok(())
+            }
+            Err(SendError::Full(_)) => Err(SendError::Full(req)),
+            Err(SendError::OutOfRange(_)) => Err(SendError::OutOfRange(req)),
+        }
+    }
+
+    fn try_recv(&mut self) -> Option<Request> {
+        self.ret_queue.pop_front()
+    }
+
+    fn pending_requests(&self) -> usize {
+        self.memory.pending_requests() + self.hit_queue.len() + self.writeback_queue.len()
+    }
+
+    fn finish(&mut self) {
+        self.memory.finish()
+    }
+
+    fn get_spec(&self) -> &Self::T {
+        self.memory.get_spec()
+    }
+
+    fn decode_addr(&self, addr: u64) -> Vec<u64> {
+        self.memory.decode_addr(addr)
+    }
+
+    fn encode_addr(&self, addr: &[u64]) -> u64 {
+        self.memory.encode_addr(addr)
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.memory.capacity_bytes()
+    }
+
+    fn has_callback(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, ddr4::DDR4, memory::SimpleMemory};
+
+    fn new_cache(config: CacheConfig) -> Cache<SimpleMemory<DDR4>> {
+        let memory_config = Config::default();
+        let ddr4 = DDR4::new(&memory_config).unwrap();
+        Cache::new(config, SimpleMemory::new(memory_config, ddr4)).unwrap()
+    }
+
+    /// send the request and wait for it
+    fn access<M: MemoryTrait>(cache: &mut Cache<M>, req: Request) -> u64 {
+        let start = cache.get_cycle();
+        cache.try_send(req).unwrap();
+        loop {
+            cache.tick();
+            if cache.try_recv().is_some() {
+                return cache.get_cycle() - start;
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_and_miss() {
+        let mut cache = new_cache(Default::default());
+        let miss = access(&mut cache, Request::new_read(0x1000));
+        let hit = access(&mut cache, Request::new_read(0x1020));
+        assert_eq!(hit, 10);
+        assert!(miss > hit);
+        assert_eq!(cache.stats.misses, 1);
+        assert_eq!(cache.stats.hits, 1);
+
+        // the misses to the same line share the mshr
+        cache.try_send(Request::new_read(0x2000)).unwrap();
+        cache.try_send(Request::new_write(0x2008)).unwrap();
+        let mut returned = 0;
+        while returned < 2 {
+            cache.tick();
+            while cache.try_recv().is_some() {
+                returned += 1;
+            }
+        }
+        assert_eq!(cache.stats.misses, 2);
+        assert_eq!(cache.stats.mshr_hits, 1);
+    }
+
+    #[test]
+    fn test_cache_dirty_eviction() {
+        // a single set of 2 ways
+        let mut cache = new_cache(CacheConfig {
+            size: 128,
+            ways: 2,
+            ..Default::default()
+        });
+        access(&mut cache, Request::new_write(0));
+        access(&mut cache, Request::new_read(64));
+        access(&mut cache, Request::new_read(0));
+        // the lru line is 64, it's clean
+        access(&mut cache, Request::new_read(128));
+        assert_eq!(cache.stats.writebacks, 0);
+        // the lru line is 0 now, it's dirty
+        access(&mut cache, Request::new_read(192));
+        assert_eq!(cache.stats.writebacks, 1);
+        assert_eq!(cache.stats.misses, 4);
+        assert_eq!(cache.stats.hits, 1);
+        for _ in 0..100 {
+            cache.tick();
+        }
+        assert_eq!(cache.memory().pending_requests(), 0);
+    }
+
+    #[test]
+    fn test_cache_sources() {
+        // the fetches and the writebacks are counted for the sources behind the cache
+        let mut cache = new_cache(CacheConfig {
+            size: 128,
+            ways: 2,
+            ..Default::default()
+        });
+        access(&mut cache, Request::new_write(0).with_source(1));
+        access(&mut cache, Request::new_read(64).with_source(2));
+        // evict the dirty line written by source 1
+        access(&mut cache, Request::new_read(128).with_source(2));
+        for _ in 0..100 {
+            cache.tick();
+        }
+        let sources = cache.memory().source_stats();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].requests(), 0);
+        assert_eq!((sources[1].reads, sources[1].writes), (1, 1));
+        assert_eq!((sources[2].reads, sources[2].writes), (2, 0));
+    }
+
+    #[test]
+    fn test_invalid_cache() {
+        let memory_config = Config::default();
+        let ddr4 = DDR4::new(&memory_config).unwrap();
+        let mut memory = SimpleMemory::new(memory_config, ddr4);
+        let config = CacheConfig {
+            size: 3 << 20,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Cache::new(config, &mut memory),
+            Err(Error::InvalidCache(_))
+        ));
+        for config in [
+            CacheConfig {
+                ways: 0,
+                ..Default::default()
+            },
+            CacheConfig {
+                line_size: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                Cache::new(config, &mut memory),
+                Err(Error::InvalidCache(_))
+            ));
+        }
+        memory.set_callback(|_| {});
+        assert!(matches!(
+            Cache::new(Default::default(), &mut memory),
+            Err(Error::CallbackSet)
+        ));
+    }
+}
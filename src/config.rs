@@ -1,22 +1,120 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    ddr4::{DDR4Org, Speed},
-    memory::MappingType,
+    controller::ControllerConfig,
+    ddr4::DDR4Config,
+    error::{ConfigIssue, Error},
+    memory::{MappingType, OutOfRangePolicy},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub channels: usize,
     pub ranks: usize,
-    pub ddr4_org: DDR4Org,
-    pub ddr4_speed: Speed,
     pub mapping_type: MappingType,
+    pub dram: DramConfig,
+    /// the bytes of a request, should be a power of two
+    /// - a request smaller than a burst(like a 32B sector) still takes one burst
+    /// - a request larger than a burst(like a 128B line) takes several bursts to consecutive columns
+    #[serde(default = "default_request_size")]
+    pub request_size: usize,
+    /// what to do with a request whose addr is out of the capacity, wrap by default
+    #[serde(default)]
+    pub out_of_range: OutOfRangePolicy,
+    /// override the timings of the speed bin before building the dram, the names are
+    /// standard specific, like `nRCD` for DDR4
+    /// ## Example
+    /// ```toml
+    /// [timing]
+    /// nRCD = 20
+    /// nFAW = 30
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub timing: BTreeMap<String, f64>,
+    /// the queue depths and write watermarks, shared by all the channels
+    #[serde(default)]
+    pub controller: ControllerConfig,
+}
+
+/// the dram standard and its standard specific config, selected by the `standard` key
+/// ## Example
+/// ```toml
+/// [dram]
+/// standard = "DDR4"
+/// org = "DDR4_4Gb_x8"
+/// speed = "DDR4_2400R"
+/// ```
+#[derive(Debug, Serialize, Deserialize, enum_as_inner::EnumAsInner)]
+#[serde(tag = "standard")]
+pub enum DramConfig {
+    DDR4(DDR4Config),
+}
+impl DramConfig {
+    /// the name of the dram standard
+    pub fn standard(&self) -> &'static str {
+        match self {
+            DramConfig::DDR4(_) => "DDR4",
+        }
+    }
+}
+
+fn default_request_size() -> usize {
+    64
 }
 
 impl Config {
-    pub fn from_toml_path(path: &str) -> Self {
-        toml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    pub fn from_toml_path(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_toml_str(&content)
+    }
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// check the config before building the memory, report all the problems found
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut issues = vec![];
+        for (field, value) in [("channels", self.channels), ("ranks", self.ranks)] {
+            if !value.is_power_of_two() {
+                issues.push(ConfigIssue::new(
+                    field,
+                    format!("should be a power of two, got {}", value),
+                ));
+            }
+        }
+        issues.extend(self.request_size_issue());
+        if let Some(bits) = self.mapping_type.segment_bits().filter(|bits| *bits > 64) {
+            issues.push(ConfigIssue::new(
+                "mapping_type",
+                format!(
+                    "the segments have {} bits, more than the 64 bits of an addr",
+                    bits
+                ),
+            ));
+        }
+        self.controller.validate(&mut issues);
+        match &self.dram {
+            DramConfig::DDR4(ddr4_config) => ddr4_config.validate(self, &mut issues),
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(issues))
+        }
+    }
+    /// the request size should be a power of two, checked by the dram standards too
+    pub(crate) fn request_size_issue(&self) -> Option<ConfigIssue> {
+        (!self.request_size.is_power_of_two()).then(|| {
+            ConfigIssue::new(
+                "request_size",
+                format!("should be a power of two, got {}", self.request_size),
+            )
+        })
     }
 }
 
@@ -26,14 +124,19 @@ impl Default for Config {
             channels: 1,
             ranks: 1,
             mapping_type: MappingType::ChRaBaRoCo,
-            ddr4_org: DDR4Org::DDR4_4Gb_x8,
-            ddr4_speed: Speed::DDR4_2400R,
+            dram: DramConfig::DDR4(Default::default()),
+            request_size: default_request_size(),
+            out_of_range: Default::default(),
+            timing: Default::default(),
+            controller: Default::default(),
         }
     }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ddr4::{DDR4Config, DDR4Org, DDR4OrgConfig, DDR4Organization};
+    use crate::memory::BitMapping;
     #[test]
     #[ignore]
     fn dum_config() {
@@ -41,4 +144,175 @@ mod tests {
         let toml = toml::to_string(&config).unwrap();
         println!("{}", toml);
     }
+
+    #[test]
+    fn test_parse_ddr4_config() {
+        let config: Config = toml::from_str(
+            r#"
+            channels = 2
+            ranks = 1
+            mapping_type = "RoBaRaCoCh"
+            [dram]
+            standard = "DDR4"
+            org = "DDR4_8Gb_x16"
+            speed = "DDR4_3200"
+            [timing]
+            nRCD = 20
+            tCK = 0.7
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.channels, 2);
+        let ddr4_config = config.dram.as_ddr4().unwrap();
+        assert!(matches!(
+            ddr4_config.org,
+            DDR4OrgConfig::Preset(DDR4Org::DDR4_8Gb_x16)
+        ));
+        assert!(matches!(ddr4_config.speed, crate::ddr4::Speed::DDR4_3200));
+        assert_eq!(config.timing["nRCD"], 20.);
+        assert_eq!(config.timing["tCK"], 0.7);
+    }
+
+    #[test]
+    fn test_parse_ddr4_organization() {
+        let config: Config = toml::from_str(
+            r#"
+            channels = 1
+            ranks = 1
+            mapping_type = { Bits = "Ro:17 Bg Ba:2 Co:4 Bg Co:3" }
+            [dram]
+            standard = "DDR4"
+            speed = "DDR4_3200"
+            [dram.organization]
+            bank_groups = 4
+            banks_per_group = 4
+            rows = 131072
+            columns = 1024
+            dq = 8
+            "#,
+        )
+        .unwrap();
+        let ddr4_config = config.dram.as_ddr4().unwrap();
+        assert_eq!(
+            ddr4_config.org.organization().unwrap(),
+            DDR4Org::DDR4_16Gb_x8.organization().unwrap()
+        );
+        assert_eq!(
+            config.mapping_type.get_slice_sequence(true),
+            None,
+            "bit mapping is not sliced by whole levels"
+        );
+    }
+
+    #[test]
+    fn test_parse_xor_mapping() {
+        let config: Config = toml::from_str(
+            r#"
+            channels = 1
+            ranks = 1
+            [mapping_type.Xor]
+            base = "RoBaRaCoCh"
+            hashes = [{ level = "Ba", masks = [0x800, 0x1000] }]
+            [dram]
+            standard = "DDR4"
+            org = "DDR4_4Gb_x8"
+            speed = "DDR4_2400R"
+            "#,
+        )
+        .unwrap();
+        let xor_mapping = config.mapping_type.as_xor().unwrap();
+        assert!(matches!(*xor_mapping.base, MappingType::RoBaRaCoCh));
+        assert_eq!(xor_mapping.hashes[0].masks, vec![0x800, 0x1000]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::default().validate().is_ok());
+        let mut config = Config {
+            channels: 3,
+            ranks: 8,
+            request_size: 96,
+            ..Default::default()
+        };
+        config.timing.insert("nFOO".to_string(), 1.);
+        let Err(Error::InvalidConfig(issues)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(fields, ["channels", "request_size", "timing.nFOO", "ranks"]);
+
+        let config = Config {
+            mapping_type: MappingType::Bits(BitMapping::new("Ro:15 Ba:2 Co:7").unwrap()),
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        assert_eq!(issues[0].field, "mapping_type");
+
+        let config = Config {
+            mapping_type: MappingType::Bits(BitMapping::new("Ro:65").unwrap()),
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("65 bits"));
+
+        let config = Config {
+            dram: DramConfig::DDR4(DDR4Config {
+                org: DDR4OrgConfig::Custom(DDR4Organization {
+                    dq: 0,
+                    ..DDR4Org::DDR4_4Gb_x8.organization().unwrap()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "dram.organization");
+
+        let config = Config {
+            controller: ControllerConfig {
+                read_queue: 0,
+                write_high_watermark: 0.2,
+                write_low_watermark: 0.8,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = config.validate() else {
+            panic!("the config should be invalid");
+        };
+        let fields: Vec<_> = issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            ["controller.read_queue", "controller.write_low_watermark"]
+        );
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let err = Config::from_toml_path("not_exist.toml").unwrap_err();
+        assert!(matches!(err, Error::Io { .. }), "{}", err);
+        let err = Config::from_toml_str("channels = 1\nranks = ").unwrap_err();
+        assert!(matches!(err, Error::Parse(_)), "{}", err);
+        let err = Config::from_toml_str(
+            r#"
+            channels = 1
+            ranks = 1
+            mapping_type = "ChRaBaRoCo"
+            [dram]
+            standard = "DDR4"
+            org = "DDR4_4Gb_x9"
+            speed = "DDR4_2400R"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("DDR4_4Gb_x9"), "{}", err);
+    }
 }
@@ -1,14 +1,106 @@
 use std::collections::VecDeque;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     config::Config,
     dram::{CommandTrait, Dram, DramSpec},
+    error::ConfigIssue,
     refresh::Refresh,
     request::{ReqType, Request},
     rowpolicy::RowPolicy,
     rowtable::RowTable,
     scheduler::Scheduler,
 };
+/// the queue depths and write watermarks of each controller
+/// ## Example
+/// ```toml
+/// [controller]
+/// read_queue = 32
+/// write_queue = 64
+/// write_high_watermark = 0.75
+/// write_low_watermark = 0.25
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControllerConfig {
+    pub read_queue: usize,
+    pub write_queue: usize,
+    /// the activated requests waiting for their read or write, an activate stalls when it's full
+    pub act_queue: usize,
+    /// switch to writing when the write queue is fuller than this fraction
+    pub write_high_watermark: f32,
+    /// switch back to reading when the write queue is emptier than this fraction
+    pub write_low_watermark: f32,
+    /// serve a read from a queued write to the same addr instead of accessing the dram, off by default
+    pub forward_reads: bool,
+    /// the cycles to serve a read from a write to the same addr in the write queue
+    pub read_forward_latency: u64,
+    /// merge the duplicated reads and coalesce the writes to the same addr, off by default
+    pub merge_requests: bool,
+    /// when to acknowledge a write, at the issue by default
+    pub write_ack: WriteAck,
+}
+
+/// when a write is acknowledged and returned by the memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WriteAck {
+    /// when the write command is issued, like a posted write
+    #[default]
+    Issue,
+    /// when the write data is done, nCWL + nBL after the write command
+    Complete,
+}
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        Self {
+            read_queue: 512,
+            write_queue: 512,
+            act_queue: 512,
+            write_high_watermark: 0.8,
+            write_low_watermark: 0.2,
+            forward_reads: false,
+            read_forward_latency: 1,
+            merge_requests: false,
+            write_ack: Default::default(),
+        }
+    }
+}
+impl ControllerConfig {
+    /// the controller part of `Config::validate`
+    pub(crate) fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        for (field, depth) in [
+            ("controller.read_queue", self.read_queue),
+            ("controller.write_queue", self.write_queue),
+            ("controller.act_queue", self.act_queue),
+        ] {
+            if depth == 0 {
+                issues.push(ConfigIssue::new(field, "the queue depth should not be 0"));
+            }
+        }
+        for (field, watermark) in [
+            ("controller.write_high_watermark", self.write_high_watermark),
+            ("controller.write_low_watermark", self.write_low_watermark),
+        ] {
+            if !(0.0..=1.0).contains(&watermark) {
+                issues.push(ConfigIssue::new(
+                    field,
+                    format!("should be in [0, 1], got {}", watermark),
+                ));
+            }
+        }
+        if self.write_low_watermark > self.write_high_watermark {
+            issues.push(ConfigIssue::new(
+                "controller.write_low_watermark",
+                format!(
+                    "{} is higher than the write_high_watermark {}",
+                    self.write_low_watermark, self.write_high_watermark
+                ),
+            ));
+        }
+    }
+}
+
 pub struct Queue {
     pub queue: VecDeque<Request>,
     max: usize,
@@ -21,6 +113,7 @@ impl Default for Queue {
         }
     }
 }
+#[derive(Debug, Clone, Copy)]
 enum QueueType {
     Read,
     Write,
@@ -41,6 +134,25 @@ impl Queue {
         self.queue.len() >= self.max
     }
 }
+/// the statistics of a controller
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControllerStats {
+    /// the reads served by the write queue without accessing the dram
+    pub forwarded_reads: u64,
+    /// the reads merged into an outstanding read to the same addr
+    pub merged_reads: u64,
+    /// the writes coalesced into a queued write to the same addr
+    pub merged_writes: u64,
+}
+impl ControllerStats {
+    /// add up the statistics of another controller
+    pub fn merge(&mut self, other: &ControllerStats) {
+        self.forwarded_reads += other.forwarded_reads;
+        self.merged_reads += other.merged_reads;
+        self.merged_writes += other.merged_writes;
+    }
+}
+
 pub enum RunningMode {
     Reading,
     Writing,
@@ -55,59 +167,137 @@ pub struct Controller<T: DramSpec> {
     pub write_queue: Queue,
     pub act_queue: Queue,
     pub other_queue: Queue,
+    /// the issued requests waiting for their data, bounded by the issue rate and the latency
     pub pending_queue: VecDeque<Request>,
     pub finished_queue: VecDeque<Request>,
     pub running_mode: RunningMode,
     pub wr_hight_watermark: f32,
     pub wr_low_watermark: f32,
+    pub forward_reads: bool,
+    pub read_forward_latency: u64,
+    pub merge_requests: bool,
+    pub write_ack: WriteAck,
+    pub stats: ControllerStats,
+    /// the cycle of the last tick
+    clk: u64,
 }
 
 impl<T> Controller<T>
 where
     T: DramSpec,
 {
-    pub fn new(_config: &Config, dram: Dram<T>) -> Self {
+    pub fn new(config: &Config, dram: Dram<T>) -> Self {
+        let controller_config = &config.controller;
         Self {
             channel: dram,
             scheduler: Default::default(),
             row_policy: Default::default(),
             row_table: Default::default(),
             refresh: Default::default(),
-            read_queue: Default::default(),
-            write_queue: Default::default(),
-            act_queue: Default::default(),
+            read_queue: Queue::new(controller_config.read_queue),
+            write_queue: Queue::new(controller_config.write_queue),
+            act_queue: Queue::new(controller_config.act_queue),
             other_queue: Default::default(),
             pending_queue: Default::default(),
             finished_queue: Default::default(),
             running_mode: RunningMode::Reading,
-            wr_hight_watermark: 0.8,
-            wr_low_watermark: 0.2,
+            wr_hight_watermark: controller_config.write_high_watermark,
+            wr_low_watermark: controller_config.write_low_watermark,
+            forward_reads: controller_config.forward_reads,
+            read_forward_latency: controller_config.read_forward_latency,
+            merge_requests: controller_config.merge_requests,
+            write_ack: controller_config.write_ack,
+            stats: Default::default(),
+            clk: 0,
         }
     }
     pub fn finish(_read_req: u64, _dram_cycles: u64) {
         todo!("implement me")
     }
-    pub fn try_enqueue(&mut self, req: Request) -> Result<(), Request> {
+    pub fn try_enqueue(&mut self, mut req: Request) -> Result<(), Request> {
         assert!(req.done_setup);
+        // a read to the addr of a queued write is served by the write, the write may have
+        // been activated and moved to the act queue
+        if self.forward_reads
+            && req.req_type.is_read()
+            && self
+                .write_queue
+                .queue
+                .iter()
+                .chain(self.act_queue.queue.iter())
+                .any(|write| write.req_type.is_write() && write.addr_vec == req.addr_vec)
+        {
+            req.finish_time = self.clk + self.read_forward_latency;
+            tracing::debug!(?req, "read forwarded from the write queue");
+            self.stats.forwarded_reads += 1;
+            self.pending_queue.push_back(req);
+            return Ok(());
+        }
+        // a duplicated request waits for the queued one instead of accessing the dram again
+        if let Some(target) = self.find_merge_target(&req) {
+            tracing::debug!(?req, "request merged");
+            let req_type = req.req_type;
+            target.merged.push(req);
+            match req_type {
+                ReqType::Read => self.stats.merged_reads += 1,
+                ReqType::Write => self.stats.merged_writes += 1,
+            }
+            return Ok(());
+        }
         let queue = match req.req_type {
             ReqType::Read => &mut self.read_queue,
             ReqType::Write => &mut self.write_queue,
         };
         if queue.full() {
             return Err(req);
-        } else {
-            queue.queue.push_back(req);
         }
+        queue.queue.push_back(req);
 
         Ok(())
     }
+    /// find a queued request of the same type and addr that `req` can be merged into,
+    /// a write that has issued some bursts can't take new data
+    fn find_merge_target(&mut self, req: &Request) -> Option<&mut Request> {
+        if !self.merge_requests {
+            return None;
+        }
+        let queue = match req.req_type {
+            ReqType::Read => &mut self.read_queue,
+            ReqType::Write => &mut self.write_queue,
+        };
+        queue
+            .queue
+            .iter_mut()
+            .chain(self.act_queue.queue.iter_mut())
+            .find(|queued| {
+                queued.addr_vec == req.addr_vec
+                    && match (queued.req_type, req.req_type) {
+                        (ReqType::Read, ReqType::Read) => true,
+                        (ReqType::Write, ReqType::Write) => queued.issued_bursts == 0,
+                        _ => false,
+                    }
+            })
+    }
+    /// move the request and the requests merged into it to the finished queue
+    fn finish_req(&mut self, mut req: Request) {
+        let merged = std::mem::take(&mut req.merged);
+        let finish_time = req.finish_time;
+        self.finished_queue.push_back(req);
+        for mut merged in merged {
+            merged.finish_time = finish_time;
+            self.finished_queue.push_back(merged);
+        }
+    }
     pub fn tick(&mut self, spec: &T, clk: u64) {
-        // serve pending requests
-        if let Some(req) = self.pending_queue.pop_front() {
-            if req.finish_time <= clk {
-                self.finished_queue.push_back(req);
+        self.clk = clk;
+        // serve pending requests, writes may finish before the reads issued earlier
+        let mut index = 0;
+        while index < self.pending_queue.len() {
+            if self.pending_queue[index].finish_time <= clk {
+                let req = self.pending_queue.remove(index).unwrap();
+                self.finish_req(req);
             } else {
-                self.pending_queue.push_front(req);
+                index += 1;
             }
         }
         // serve refresh
@@ -132,37 +322,50 @@ where
                 }
             }
         }
-        // find the best command to schedule
-        if let Some((index, req)) = self.scheduler.get_best_req(&self.act_queue, &self.channel) {
-            let cmd = self.get_first_cmd(spec, req);
-            let is_last = cmd == T::get_first_cmd(&req.req_type);
-            if self.is_ready_cmd(spec, &cmd, &req.addr_vec, clk) {
-                self.issue_cmd(spec, cmd, &req.addr_vec.clone(), clk);
-                self.handle_after_issue(spec, index, &cmd, is_last, QueueType::Act, clk);
-                return;
-            } else {
-                // not find any valid command in act queue
-                //tracing::debug!(?req, "not ready in act queue")
+        // find the best command to schedule, at most one command per command bus,
+        // try the act queue first and then the read/write queue until nothing can be issued
+        let mut bus_busy = vec![false; spec.get_cmd_bus_num()];
+        while !bus_busy.iter().all(|busy| *busy) {
+            if self.try_issue(spec, QueueType::Act, &mut bus_busy, clk) {
+                continue;
+            }
+            let queue_type = self.get_best_queue_type();
+            if !self.try_issue(spec, queue_type, &mut bus_busy, clk) {
+                break;
             }
         }
-        // not find the act queue req
-        let (queue, queue_type) = self.get_best_queue();
+    }
+    /// try to issue the first command of the best request in the queue,
+    /// return true if a command is issued
+    fn try_issue(
+        &mut self,
+        spec: &T,
+        queue_type: QueueType,
+        bus_busy: &mut [bool],
+        clk: u64,
+    ) -> bool {
+        let queue = self.get_queue(queue_type);
         if let Some((index, req)) = self.scheduler.get_best_req(queue, &self.channel) {
             let cmd = self.get_first_cmd(spec, req);
+            let bus = spec.get_cmd_bus(&cmd);
+            if bus_busy[bus] {
+                // the bus is already used in this cycle
+                return false;
+            }
             let is_last = cmd == T::get_first_cmd(&req.req_type);
+            if cmd.is_act() && !matches!(queue_type, QueueType::Act) && self.act_queue.full() {
+                // no room for another activated request
+                return false;
+            }
             if self.is_ready_cmd(spec, &cmd, &req.addr_vec, clk) {
                 // pop the request from the queue
                 self.issue_cmd(spec, cmd, &req.addr_vec.clone(), clk);
                 self.handle_after_issue(spec, index, &cmd, is_last, queue_type, clk);
-                return;
-            } else {
-                // not ready
-                //tracing::debug!(?req, "not ready in rd/wr queue");
-                return;
+                bus_busy[bus] = true;
+                return true;
             }
         }
-
-        // find read and write queue
+        false
     }
     pub fn is_ready_req(&self, _cmd: &Request) -> bool {
         todo!("implement me")
@@ -192,7 +395,7 @@ where
     fn get_first_cmd(&self, spec: &T, req: &Request) -> T::Command {
         let frist_cmd = self.channel.get_first_cmd(&req.req_type);
         tracing::debug!(?frist_cmd, "the init cmd");
-        return self.channel.decode(spec, &frist_cmd, &req.addr_vec);
+        self.channel.decode(spec, &frist_cmd, &req.addr_vec)
     }
     fn issue_cmd(&mut self, spec: &T, cmd: T::Command, addr_vec: &[u64], clk: u64) {
         tracing::debug!(?cmd, clk, "issue cmd");
@@ -207,14 +410,16 @@ where
         queue_type: QueueType,
         clk: u64,
     ) {
+        // the first command of a row hit is the read or write itself
+        let queue = self.get_queue_mut(queue_type);
+        queue.queue[cmd_index].row_hit.get_or_insert(is_last);
         // check if the request is finished
         if is_last {
-            let queue = match queue_type {
-                QueueType::Read => &mut self.read_queue,
-                QueueType::Write => &mut self.write_queue,
-                QueueType::Act => &mut self.act_queue,
-                QueueType::Other => &mut self.other_queue,
-            };
+            queue.queue[cmd_index].issued_bursts += 1;
+            if queue.queue[cmd_index].issued_bursts < spec.get_request_bursts() {
+                // still have bursts to issue
+                return;
+            }
             let mut req = queue.queue.remove(cmd_index).unwrap();
             match req.req_type {
                 ReqType::Read => {
@@ -223,34 +428,54 @@ where
 
                     self.pending_queue.push_back(req);
                 }
-                ReqType::Write => {
-                    req.finish_time = clk;
-                    tracing::debug!(?req, "write request finished");
+                ReqType::Write => match self.write_ack {
+                    WriteAck::Issue => {
+                        req.finish_time = clk;
+                        tracing::debug!(?req, "write request acknowledged");
 
-                    self.finished_queue.push_back(req);
-                }
-            }
-        } else {
-            if cmd.is_act() {
-                let queue = match queue_type {
-                    QueueType::Read => &mut self.read_queue,
-                    QueueType::Write => &mut self.write_queue,
-                    QueueType::Act => &mut self.act_queue,
-                    QueueType::Other => &mut self.other_queue,
-                };
-                let req = queue.queue.remove(cmd_index).unwrap();
-                self.act_queue.queue.push_back(req);
+                        self.finish_req(req);
+                    }
+                    WriteAck::Complete => {
+                        req.finish_time = clk + spec.get_write_latency();
+                        tracing::debug!(?req, clk, req.finish_time, "write request finished");
+
+                        self.pending_queue.push_back(req);
+                    }
+                },
             }
+        } else if cmd.is_act() {
+            let req = self
+                .get_queue_mut(queue_type)
+                .queue
+                .remove(cmd_index)
+                .unwrap();
+            self.act_queue.queue.push_back(req);
         }
     }
 
-    fn get_best_queue(&self) -> (&Queue, QueueType) {
+    fn get_queue(&self, queue_type: QueueType) -> &Queue {
+        match queue_type {
+            QueueType::Read => &self.read_queue,
+            QueueType::Write => &self.write_queue,
+            QueueType::Act => &self.act_queue,
+            QueueType::Other => &self.other_queue,
+        }
+    }
+    fn get_queue_mut(&mut self, queue_type: QueueType) -> &mut Queue {
+        match queue_type {
+            QueueType::Read => &mut self.read_queue,
+            QueueType::Write => &mut self.write_queue,
+            QueueType::Act => &mut self.act_queue,
+            QueueType::Other => &mut self.other_queue,
+        }
+    }
+    fn get_best_queue_type(&self) -> QueueType {
         match self.other_queue.size() {
             0 => match self.running_mode {
-                RunningMode::Reading => (&self.read_queue, QueueType::Read),
-                RunningMode::Writing => (&self.write_queue, QueueType::Write),
+                RunningMode::Reading => QueueType::Read,
+                RunningMode::Writing => QueueType::Write,
             },
-            _ => (&self.other_queue, QueueType::Other),
+            _ => QueueType::Other,
         }
     }
     #[allow(dead_code)]
@@ -267,18 +492,135 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::ddr4::{Level, DDR4};
-    use crate::dram::LevelTrait;
+    use crate::config::DramConfig;
+    use crate::ddr4::{Command, DDR4Config, Level, Speed, DDR4};
+    use crate::dram::{LevelTrait, State, TimeEntry};
     use crate::init_logger;
+    use crate::memory::MappingType;
 
     use super::*;
 
+    /// DDR4 timings with several command buses
+    struct MultiBusDDR4 {
+        ddr4: DDR4,
+        bus_num: usize,
+        bus: fn(&Command) -> usize,
+    }
+    impl MultiBusDDR4 {
+        /// separate row and column buses, like HBM
+        fn row_column(ddr4: DDR4) -> Self {
+            Self {
+                ddr4,
+                bus_num: 2,
+                bus: |cmd| match cmd {
+                    Command::RD | Command::WR | Command::RDA | Command::WRA => 1,
+                    _ => 0,
+                },
+            }
+        }
+        /// the activates, the other row commands and the column commands on their own buses
+        fn act_row_column(ddr4: DDR4) -> Self {
+            Self {
+                ddr4,
+                bus_num: 3,
+                bus: |cmd| match cmd {
+                    Command::ACT => 0,
+                    Command::RD | Command::WR | Command::RDA | Command::WRA => 2,
+                    _ => 1,
+                },
+            }
+        }
+    }
+    impl DramSpec for MultiBusDDR4 {
+        type Level = Level;
+        type Command = Command;
+        fn get_first_cmd(req_type: &ReqType) -> Command {
+            DDR4::get_first_cmd(req_type)
+        }
+        // only the bank states are needed by the tests
+        fn get_pre_cmd(dram: &Dram<Self>, cmd: &Command, child_id: u64) -> Option<Command> {
+            match (dram.level, &dram.state) {
+                (Level::Bank, State::Closed) => Some(Command::ACT),
+                (Level::Bank, State::Opened(row_id)) if *row_id == child_id => Some(*cmd),
+                (Level::Bank, State::Opened(_)) => Some(Command::PRE),
+                _ => None,
+            }
+        }
+        fn get_start_state(level: &Level) -> State {
+            DDR4::get_start_state(level)
+        }
+        fn decode_addr(&self, addr: u64, mapping_type: &MappingType) -> Vec<u64> {
+            self.ddr4.decode_addr(addr, mapping_type)
+        }
+        fn encode_addr(&self, addr: &[u64], mapping_type: &MappingType) -> u64 {
+            self.ddr4.encode_addr(addr, mapping_type)
+        }
+        fn get_addr_bits(&self, level: &Level) -> usize {
+            self.ddr4.get_addr_bits(level)
+        }
+        fn get_full_addr_bits(&self) -> &[usize] {
+            self.ddr4.get_full_addr_bits()
+        }
+        fn get_addr_size(&self, level: &Level) -> usize {
+            self.ddr4.get_addr_size(level)
+        }
+        fn get_full_addr_size(&self) -> &[usize] {
+            self.ddr4.get_full_addr_size()
+        }
+        fn get_scope(&self, cmd: &Command) -> Level {
+            self.ddr4.get_scope(cmd)
+        }
+        fn update_state(&self, dram: &mut Dram<Self>, cmd: &Command, child_id: u64) {
+            match (dram.level, cmd) {
+                (Level::Bank, Command::ACT) => dram.state = State::Opened(child_id),
+                (Level::Bank, Command::PRE) => dram.state = State::Closed,
+                _ => {}
+            }
+        }
+        fn get_timming(&self, level: &Level, cmd: &Command) -> &[TimeEntry<Command>] {
+            self.ddr4.get_timming(level, cmd)
+        }
+        fn get_read_latency(&self) -> u64 {
+            self.ddr4.get_read_latency()
+        }
+        fn get_tck_ns(&self) -> f64 {
+            self.ddr4.get_tck_ns()
+        }
+        fn get_write_latency(&self) -> u64 {
+            self.ddr4.get_write_latency()
+        }
+        fn get_prefetch_size(&self) -> usize {
+            self.ddr4.get_prefetch_size()
+        }
+        fn get_channel_width(&self) -> usize {
+            self.ddr4.get_channel_width()
+        }
+        fn get_offset_bits(&self) -> usize {
+            self.ddr4.get_offset_bits()
+        }
+        fn get_request_bursts(&self) -> usize {
+            self.ddr4.get_request_bursts()
+        }
+        fn get_cmd_bus_num(&self) -> usize {
+            self.bus_num
+        }
+        fn get_cmd_bus(&self, cmd: &Command) -> usize {
+            (self.bus)(cmd)
+        }
+    }
+
     #[test]
     fn test_controller_simple_read() {
         // act and rd
         init_logger();
-        let config = Config::default();
-        let ddr4 = DDR4::new(&config);
+        let config = Config {
+            dram: DramConfig::DDR4(DDR4Config {
+                speed: Speed::DDR4_3200,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
         let dram = Dram::new(&ddr4, Level::channel(), 0);
         let mut controller = Controller::new(&config, dram);
         let req = Request {
@@ -288,6 +630,12 @@ mod tests {
             addr: 0,
             done_setup: true,
             arrival_time: 0,
+            issued_bursts: 0,
+            merged: vec![],
+            source_id: 0,
+            id: 0,
+            tag: 0,
+            row_hit: None,
         };
         controller.try_enqueue(req).unwrap();
         // the first command should be act
@@ -301,4 +649,332 @@ mod tests {
         controller.tick(&ddr4, 48);
         assert_eq!(controller.finished_queue.len(), 1);
     }
+
+    #[test]
+    fn test_controller_multi_burst_read() {
+        init_logger();
+        let config = Config {
+            request_size: 128,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        let mut req = Request::new_read(0);
+        req.addr_vec = vec![0; 6];
+        req.done_setup = true;
+        controller.try_enqueue(req).unwrap();
+        // act at 0, the first rd at 16(nRCD), the second rd at 22(nCCDL), finished at 42(nCL + nBL)
+        for i in 0..42 {
+            controller.tick(&ddr4, i);
+        }
+        assert!(controller.finished_queue.is_empty());
+        controller.tick(&ddr4, 42);
+        assert_eq!(controller.finished_queue.len(), 1);
+        assert_eq!(controller.finished_queue[0].issued_bursts, 2);
+        assert_eq!(controller.finished_queue[0].row_hit, Some(false));
+        // the row is still open
+        let mut req = Request::new_read(0);
+        req.addr_vec = vec![0, 0, 0, 0, 0, 1];
+        req.done_setup = true;
+        controller.try_enqueue(req).unwrap();
+        for i in 43..100 {
+            controller.tick(&ddr4, i);
+        }
+        assert_eq!(controller.finished_queue.len(), 2);
+        assert_eq!(controller.finished_queue[1].row_hit, Some(true));
+    }
+
+    #[test]
+    fn test_controller_queue_config() {
+        let config = Config {
+            controller: ControllerConfig {
+                read_queue: 2,
+                write_queue: 4,
+                write_high_watermark: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        let new_req = |req_type, row| {
+            let mut req = Request::new(0, req_type);
+            req.addr_vec = vec![0, 0, 0, 0, row, 0];
+            req.done_setup = true;
+            req
+        };
+        controller.try_enqueue(new_req(ReqType::Read, 0)).unwrap();
+        controller.try_enqueue(new_req(ReqType::Read, 1)).unwrap();
+        assert!(controller.try_enqueue(new_req(ReqType::Read, 2)).is_err());
+        for row in 0..3 {
+            controller
+                .try_enqueue(new_req(ReqType::Write, row))
+                .unwrap();
+        }
+        // 3 writes is above the high watermark(2 entries), switch to writing
+        controller.tick(&ddr4, 0);
+        assert!(matches!(controller.running_mode, RunningMode::Writing));
+    }
+
+    #[test]
+    fn test_controller_act_queue_depth() {
+        // the reads to 8 banks, the cycle when all of them are finished
+        let run = |act_queue| {
+            let config = Config {
+                controller: ControllerConfig {
+                    act_queue,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let ddr4 = DDR4::new(&config).unwrap();
+            let dram = Dram::new(&ddr4, Level::channel(), 0);
+            let mut controller = Controller::new(&config, dram);
+            for bank in 0..8 {
+                let mut req = Request::new_read(0);
+                req.addr_vec = vec![0, 0, bank % 4, bank / 4, 0, 0];
+                req.done_setup = true;
+                controller.try_enqueue(req).unwrap();
+            }
+            let mut clk = 0;
+            while controller.finished_queue.len() < 8 {
+                controller.tick(&ddr4, clk);
+                assert!(controller.act_queue.size() <= act_queue);
+                clk += 1;
+            }
+            clk
+        };
+        // a single activated request serializes the banks
+        assert!(run(1) > 2 * run(8));
+    }
+
+    #[test]
+    fn test_controller_read_forwarding() {
+        let config = Config {
+            controller: ControllerConfig {
+                forward_reads: true,
+                read_forward_latency: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        let new_req = |req_type, row| {
+            let mut req = Request::new(0, req_type);
+            req.addr_vec = vec![0, 0, 0, 0, row, 0];
+            req.done_setup = true;
+            req
+        };
+        controller.tick(&ddr4, 0);
+        controller.try_enqueue(new_req(ReqType::Write, 1)).unwrap();
+        controller.try_enqueue(new_req(ReqType::Read, 1)).unwrap();
+        controller.try_enqueue(new_req(ReqType::Read, 2)).unwrap();
+        assert_eq!(controller.stats.forwarded_reads, 1);
+        assert_eq!(controller.read_queue.size(), 1);
+        controller.tick(&ddr4, 1);
+        assert!(controller.finished_queue.is_empty());
+        controller.tick(&ddr4, 2);
+        assert_eq!(controller.finished_queue.len(), 1);
+        assert_eq!(controller.finished_queue[0].addr_vec[4], 1);
+    }
+
+    #[test]
+    fn test_controller_read_forwarding_act_queue() {
+        let config = Config {
+            controller: ControllerConfig {
+                forward_reads: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        let new_req = |req_type| {
+            let mut req = Request::new(0, req_type);
+            req.addr_vec = vec![0, 0, 0, 0, 1, 0];
+            req.done_setup = true;
+            req
+        };
+        controller.try_enqueue(new_req(ReqType::Write)).unwrap();
+        // the write is activated at 0 and waits for nRCD in the act queue
+        controller.tick(&ddr4, 0);
+        assert!(controller.write_queue.queue.is_empty());
+        assert_eq!(controller.act_queue.size(), 1);
+        controller.try_enqueue(new_req(ReqType::Read)).unwrap();
+        assert_eq!(controller.stats.forwarded_reads, 1);
+        assert!(controller.read_queue.queue.is_empty());
+
+        // without forwarding, the read waits in the read queue
+        let config = Config::default();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        controller.try_enqueue(new_req(ReqType::Write)).unwrap();
+        controller.try_enqueue(new_req(ReqType::Read)).unwrap();
+        assert_eq!(controller.stats.forwarded_reads, 0);
+        assert_eq!(controller.read_queue.size(), 1);
+    }
+
+    #[test]
+    fn test_controller_write_ack() {
+        let new_req = || {
+            let mut req = Request::new_write(0);
+            req.addr_vec = vec![0; 6];
+            req.done_setup = true;
+            req
+        };
+        // act at 0, wr at 16(nRCD)
+        for (write_ack, finish_time) in [(WriteAck::Issue, 16), (WriteAck::Complete, 32)] {
+            let config = Config {
+                controller: ControllerConfig {
+                    write_ack,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let ddr4 = DDR4::new(&config).unwrap();
+            let dram = Dram::new(&ddr4, Level::channel(), 0);
+            let mut controller = Controller::new(&config, dram);
+            controller.try_enqueue(new_req()).unwrap();
+            for i in 0..finish_time {
+                controller.tick(&ddr4, i);
+            }
+            assert!(controller.finished_queue.is_empty());
+            controller.tick(&ddr4, finish_time);
+            assert_eq!(controller.finished_queue.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_controller_merge_requests() {
+        let config = Config {
+            controller: ControllerConfig {
+                merge_requests: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        let new_req = |req_type, row| {
+            let mut req = Request::new(0, req_type);
+            req.addr_vec = vec![0, 0, 0, 0, row, 0];
+            req.done_setup = true;
+            req
+        };
+        for _ in 0..3 {
+            controller.try_enqueue(new_req(ReqType::Read, 0)).unwrap();
+            controller.try_enqueue(new_req(ReqType::Write, 1)).unwrap();
+        }
+        assert_eq!(controller.read_queue.size(), 1);
+        assert_eq!(controller.write_queue.size(), 1);
+        assert_eq!(controller.stats.merged_reads, 2);
+        assert_eq!(controller.stats.merged_writes, 2);
+        // the read is moved to the act queue after the act, and still takes duplicates
+        controller.tick(&ddr4, 0);
+        controller.try_enqueue(new_req(ReqType::Read, 0)).unwrap();
+        assert_eq!(controller.act_queue.queue[0].merged.len(), 3);
+        // the read finishes at 36(nRCD + nCL + nBL), all the waiters finish together
+        for i in 1..=36 {
+            controller.tick(&ddr4, i);
+        }
+        assert_eq!(controller.finished_queue.len(), 4);
+        assert!(controller
+            .finished_queue
+            .iter()
+            .all(|req| req.req_type.is_read() && req.finish_time == 36));
+    }
+
+    #[test]
+    fn test_controller_two_cmd_buses() {
+        let config = Config::default();
+        let new_req = |bank_group| {
+            let mut req = Request::new_read(0);
+            req.addr_vec = vec![0, 0, bank_group, 0, 0, 0];
+            req.done_setup = true;
+            req
+        };
+        // the first read is activated at 0 and its rd is ready at 16(nRCD), the second read
+        // arrives at 16, the rd of the first and the act of the second want the same cycle
+        let spec = MultiBusDDR4::row_column(DDR4::new(&config).unwrap());
+        let dram = Dram::new(&spec, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        controller.try_enqueue(new_req(0)).unwrap();
+        for i in 0..16 {
+            controller.tick(&spec, i);
+        }
+        assert_eq!(controller.act_queue.size(), 1);
+        controller.try_enqueue(new_req(1)).unwrap();
+        controller.tick(&spec, 16);
+        // both are issued, the rd on the column bus and the act on the row bus
+        assert_eq!(controller.pending_queue.len(), 1);
+        assert!(controller.read_queue.queue.is_empty());
+        assert_eq!(controller.act_queue.size(), 1);
+        assert_eq!(controller.act_queue.queue[0].addr_vec[2], 1);
+
+        // with a single shared bus, the act waits for the next cycle
+        let ddr4 = DDR4::new(&config).unwrap();
+        let dram = Dram::new(&ddr4, Level::channel(), 0);
+        let mut controller = Controller::new(&config, dram);
+        controller.try_enqueue(new_req(0)).unwrap();
+        for i in 0..16 {
+            controller.tick(&ddr4, i);
+        }
+        controller.try_enqueue(new_req(1)).unwrap();
+        controller.tick(&ddr4, 16);
+        assert_eq!(controller.pending_queue.len(), 1);
+        assert_eq!(controller.read_queue.size(), 1);
+        assert!(controller.act_queue.queue.is_empty());
+    }
+
+    #[test]
+    fn test_controller_three_cmd_buses() {
+        let config = Config::default();
+        let new_req = |bank_group, row| {
+            let mut req = Request::new_read(0);
+            req.addr_vec = vec![0, 0, bank_group, 0, row, 0];
+            req.done_setup = true;
+            req
+        };
+        // open row 0 of bank group 1 and activate bank group 0 at 60, its rd is ready at 76(nRCD)
+        let run = |spec: &MultiBusDDR4| {
+            let dram = Dram::new(spec, Level::channel(), 0);
+            let mut controller = Controller::new(&config, dram);
+            controller.try_enqueue(new_req(1, 0)).unwrap();
+            for i in 0..60 {
+                controller.tick(spec, i);
+            }
+            assert_eq!(controller.finished_queue.len(), 1);
+            controller.try_enqueue(new_req(0, 0)).unwrap();
+            for i in 60..76 {
+                controller.tick(spec, i);
+            }
+            // an act to bank group 2 and a pre for row 1 of bank group 1
+            controller.try_enqueue(new_req(2, 0)).unwrap();
+            controller.try_enqueue(new_req(1, 1)).unwrap();
+            controller.tick(spec, 76);
+            controller
+        };
+        // the rd, the act and the pre are issued in the same cycle
+        let spec = MultiBusDDR4::act_row_column(DDR4::new(&config).unwrap());
+        let controller = run(&spec);
+        assert_eq!(controller.pending_queue.len(), 1);
+        assert_eq!(controller.act_queue.size(), 1);
+        assert_eq!(controller.read_queue.size(), 1);
+        let bank = &controller.channel.children[0].children[1].children[0];
+        assert!(matches!(bank.state, State::Closed));
+
+        // the pre waits for the row bus
+        let spec = MultiBusDDR4::row_column(DDR4::new(&config).unwrap());
+        let controller = run(&spec);
+        assert_eq!(controller.pending_queue.len(), 1);
+        assert_eq!(controller.act_queue.size(), 1);
+        let bank = &controller.channel.children[0].children[1].children[0];
+        assert!(matches!(bank.state, State::Opened(0)));
+    }
 }
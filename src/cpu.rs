@@ -0,0 +1,550 @@
+//! a simple core driven by the cpu trace, like the cpu trace mode of ramulator
+//! - each cycle, retire up to `width` ready instructions from the head of the window, then
+//!   insert up to `width` instructions into the window
+//! - the bubbles are ready when inserted, a load is ready when its read returns from the memory
+//! - a writeback is sent after its load and does not take a window entry
+//! - the core stalls when the window is full or the memory does not accept the request
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{Cache, CacheConfig, CacheStats},
+    error::Error,
+    memory::{dispatch, AnyMemory, MemoryTrait, SendError},
+    request::Request,
+    trace::CpuTraceEntry,
+};
+
+/// the config of a core
+/// ## Example
+/// ```toml
+/// window_size = 128
+/// width = 4
+/// frequency_ghz = 3.2
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoreConfig {
+    /// the entries of the instruction window
+    pub window_size: usize,
+    /// the instructions inserted and retired per cycle
+    pub width: usize,
+    pub frequency_ghz: f64,
+}
+impl Default for CoreConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 128,
+            width: 4,
+            frequency_ghz: 3.2,
+        }
+    }
+}
+impl CoreConfig {
+    pub fn from_toml_path(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_toml_str(&content)
+    }
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+    /// a core with an empty window or a zero width can't insert any instruction
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.window_size == 0 || self.width == 0 {
+            return Err(Error::InvalidCore(format!(
+                "the window_size {} and the width {} should not be 0",
+                self.window_size, self.width
+            )));
+        }
+        if !(self.frequency_ghz.is_finite() && self.frequency_ghz > 0.) {
+            return Err(Error::InvalidCore(format!(
+                "the frequency_ghz {} should be positive",
+                self.frequency_ghz
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// the statistics of a core
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoreStats {
+    /// the cpu cycles until all the instructions are retired
+    pub cycles: u64,
+    pub instructions: u64,
+    pub reads: u64,
+    pub writebacks: u64,
+    /// the cycles that can't insert any instruction because the window is full
+    pub window_full_cycles: u64,
+}
+impl CoreStats {
+    pub fn ipc(&self) -> f64 {
+        if self.cycles == 0 {
+            return 0.;
+        }
+        self.instructions as f64 / self.cycles as f64
+    }
+}
+
+/// an instruction in the window, a load waits for the read of its addr
+#[derive(Debug)]
+struct WindowEntry {
+    ready: bool,
+    addr: u64,
+}
+
+/// a core replaying a cpu trace
+pub struct Core<I> {
+    config: CoreConfig,
+    /// the source id of the requests sent by the core
+    id: usize,
+    trace: I,
+    trace_done: bool,
+    window: VecDeque<WindowEntry>,
+    /// the rest of the current trace entry
+    bubbles: u64,
+    read: Option<Request>,
+    writeback: Option<Request>,
+    pub stats: CoreStats,
+}
+
+impl<I> Core<I>
+where
+    I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+{
+    pub fn new(config: CoreConfig, trace: I) -> Self {
+        Self {
+            window: VecDeque::with_capacity(config.window_size),
+            config,
+            id: 0,
+            trace,
+            trace_done: false,
+            bubbles: 0,
+            read: None,
+            writeback: None,
+            stats: Default::default(),
+        }
+    }
+
+    /// all the instructions are retired
+    pub fn finished(&self) -> bool {
+        self.trace_done && self.window.is_empty() && self.read.is_none() && self.writeback.is_none()
+    }
+
+    /// a cpu cycle, send the requests to the memory
+    pub fn tick<M: MemoryTrait>(&mut self, memory: &mut M) -> Result<(), Error> {
+        if self.finished() {
+            return Ok(());
+        }
+        self.stats.cycles += 1;
+        let mut retired = 0;
+        while retired < self.config.width && self.window.front().is_some_and(|e| e.ready) {
+            self.window.pop_front();
+            retired += 1;
+        }
+        self.stats.instructions += retired as u64;
+
+        if self.window.len() >= self.config.window_size {
+            self.stats.window_full_cycles += 1;
+        }
+        let mut inserted = 0;
+        while inserted < self.config.width && self.window.len() < self.config.window_size {
+            // the writeback not accepted after its load
+            if self.read.is_none() && !self.send_writeback(memory)? {
+                break;
+            }
+            if self.bubbles == 0 && self.read.is_none() && !self.fetch()? {
+                break;
+            }
+            if self.bubbles > 0 {
+                self.bubbles -= 1;
+                self.window.push_back(WindowEntry {
+                    ready: true,
+                    addr: 0,
+                });
+                inserted += 1;
+                continue;
+            }
+            let read = self.read.take().unwrap();
+            let addr = read.addr;
+            if let Some(read) = Self::send(memory, read)? {
+                self.read = Some(read);
+                break;
+            }
+            self.stats.reads += 1;
+            self.window.push_back(WindowEntry { ready: false, addr });
+            inserted += 1;
+            if !self.send_writeback(memory)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// send the writeback of the current trace entry, return false if the memory is full
+    fn send_writeback<M: MemoryTrait>(&mut self, memory: &mut M) -> Result<bool, Error> {
+        if let Some(writeback) = self.writeback.take() {
+            if let Some(writeback) = Self::send(memory, writeback)? {
+                self.writeback = Some(writeback);
+                return Ok(false);
+            }
+            self.stats.writebacks += 1;
+        }
+        Ok(true)
+    }
+
+    /// a request returned by the memory, the first load waiting for it is ready
+    pub fn receive(&mut self, req: &Request) {
+        if req.req_type.is_write() {
+            return;
+        }
+        if let Some(entry) = self
+            .window
+            .iter_mut()
+            .find(|entry| !entry.ready && entry.addr == req.addr)
+        {
+            entry.ready = true;
+        }
+    }
+
+    /// read the next trace entry, return false at the end of the trace
+    fn fetch(&mut self) -> Result<bool, Error> {
+        if self.trace_done {
+            return Ok(false);
+        }
+        match self.trace.next().transpose()? {
+            Some(entry) => {
+                self.bubbles = entry.bubbles;
+                self.read = Some(entry.read.with_source(self.id));
+                self.writeback = entry.writeback.map(|req| req.with_source(self.id));
+                Ok(true)
+            }
+            None => {
+                self.trace_done = true;
+                Ok(false)
+            }
+        }
+    }
+
+    /// send the request, return it back if the memory is full
+    fn send<M: MemoryTrait>(memory: &mut M, req: Request) -> Result<Option<Request>, Error> {
+        match memory.try_send(req) {
+            Ok(()) => Ok(None),
+            Err(SendError::Full(req)) => Ok(Some(req)),
+            Err(SendError::OutOfRange(req)) => Err(Error::InvalidAddress {
+                addr: req.addr,
+                capacity: memory.capacity_bytes(),
+            }),
+        }
+    }
+}
+
+/// run the core until all the instructions are retired, the memory is ticked by its own clock.
+/// the memory should not have a completion callback, see [`MemoryTrait::has_callback`]
+pub fn run<I, M>(core: &mut Core<I>, memory: &mut M) -> Result<CoreStats, Error>
+where
+    I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+    M: MemoryTrait,
+{
+    if memory.has_callback() {
+        return Err(Error::CallbackSet);
+    }
+    core.config.validate()?;
+    let cpu_clk_ns = 1. / core.config.frequency_ghz;
+    let memory_clk_ns = memory.clk_ns();
+    let mut cpu_time = 0.;
+    let mut memory_time = 0.;
+    while !core.finished() {
+        core.tick(memory)?;
+        cpu_time += cpu_clk_ns;
+        while memory_time < cpu_time {
+            memory.tick();
+            memory_time += memory_clk_ns;
+            while let Some(req) = memory.try_recv() {
+                core.receive(&req);
+            }
+        }
+    }
+    Ok(core.stats.clone())
+}
+
+/// run the cores sharing the memory until all of them are done, the cores are the sources
+/// `0, 1, ...` in order and all of them should run at the same frequency
+pub fn run_cores<I, M>(cores: &mut [Core<I>], memory: &mut M) -> Result<Vec<CoreStats>, Error>
+where
+    I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+    M: MemoryTrait,
+{
+    if memory.has_callback() {
+        return Err(Error::CallbackSet);
+    }
+    let Some(first) = cores.first() else {
+        return Ok(vec![]);
+    };
+    for core in cores.iter() {
+        core.config.validate()?;
+    }
+    let frequency_ghz = first.config.frequency_ghz;
+    if let Some((id, core)) = cores
+        .iter()
+        .enumerate()
+        .find(|(_, core)| core.config.frequency_ghz != frequency_ghz)
+    {
+        return Err(Error::InvalidCore(format!(
+            "core {} runs at {} GHz, but core 0 runs at {} GHz",
+            id, core.config.frequency_ghz, frequency_ghz
+        )));
+    }
+    let cpu_clk_ns = 1. / frequency_ghz;
+    let memory_clk_ns = memory.clk_ns();
+    for (id, core) in cores.iter_mut().enumerate() {
+        core.id = id;
+    }
+    let mut cpu_time = 0.;
+    let mut memory_time = 0.;
+    while !cores.iter().all(|core| core.finished()) {
+        for core in cores.iter_mut() {
+            core.tick(memory)?;
+        }
+        cpu_time += cpu_clk_ns;
+        while memory_time < cpu_time {
+            memory.tick();
+            memory_time += memory_clk_ns;
+            while let Some(req) = memory.try_recv() {
+                if let Some(core) = cores.get_mut(req.source_id) {
+                    core.receive(&req);
+                }
+            }
+        }
+    }
+    Ok(cores.iter().map(|core| core.stats.clone()).collect())
+}
+
+impl AnyMemory {
+    /// run a core on the memory, see [`run`]
+    pub fn run_core<I>(&mut self, core: &mut Core<I>) -> Result<CoreStats, Error>
+    where
+        I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+    {
+        dispatch!(self, mem => run(core, mem))
+    }
+    /// run the cores sharing the memory, see [`run_cores`]
+    pub fn run_cores<I>(&mut self, cores: &mut [Core<I>]) -> Result<Vec<CoreStats>, Error>
+    where
+        I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+    {
+        dispatch!(self, mem => run_cores(cores, mem))
+    }
+    /// run a core on the memory behind a cache
+    pub fn run_core_with_cache<I>(
+        &mut self,
+        core: &mut Core<I>,
+        cache_config: CacheConfig,
+    ) -> Result<(CoreStats, CacheStats), Error>
+    where
+        I: Iterator<Item = Result<CpuTraceEntry, Error>>,
+    {
+        dispatch!(self, mem => {
+            let mut cache = Cache::new(cache_config, mem)?;
+            let stats = run(core, &mut cache)?;
+            Ok((stats, cache.stats))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config, ddr4::DDR4, memory::SimpleMemory, request::ReqType, trace::CpuTraceReader,
+    };
+
+    fn new_memory() -> SimpleMemory<DDR4> {
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        SimpleMemory::new(config, ddr4)
+    }
+
+    /// a memory recording the accepted requests in order
+    struct Recorder {
+        memory: SimpleMemory<DDR4>,
+        /// the type and the addr of the accepted requests
+        sent: Vec<(ReqType, u64)>,
+    }
+    impl MemoryTrait for Recorder {
+        type T = DDR4;
+        fn clk_ns(&self) -> f64 {
+            self.memory.clk_ns()
+        }
+        fn get_cycle(&self) -> u64 {
+            self.memory.get_cycle()
+        }
+        fn tick(&mut self) {
+            self.memory.tick()
+        }
+        fn try_send(&mut self, req: Request) -> Result<(), SendError> {
+            let sent = (req.req_type, req.addr);
+            self.memory.try_send(req)?;
+            self.sent.push(sent);
+            Ok(())
+        }
+        fn try_recv(&mut self) -> Option<Request> {
+            self.memory.try_recv()
+        }
+        fn pending_requests(&self) -> usize {
+            self.memory.pending_requests()
+        }
+        fn finish(&mut self) {
+            self.memory.finish()
+        }
+        fn get_spec(&self) -> &DDR4 {
+            self.memory.get_spec()
+        }
+        fn decode_addr(&self, addr: u64) -> Vec<u64> {
+            self.memory.decode_addr(addr)
+        }
+        fn encode_addr(&self, addr: &[u64]) -> u64 {
+            self.memory.encode_addr(addr)
+        }
+        fn capacity_bytes(&self) -> u64 {
+            self.memory.capacity_bytes()
+        }
+        fn has_callback(&self) -> bool {
+            self.memory.has_callback()
+        }
+    }
+
+    #[test]
+    fn test_core_bubbles() {
+        // the bubbles retire 4 per cycle, only the last load waits for the memory
+        let trace = "3999 0x0\n";
+        let mut core = Core::new(Default::default(), CpuTraceReader::new(trace.as_bytes()));
+        let stats = run(&mut core, &mut new_memory()).unwrap();
+        assert_eq!(stats.instructions, 4000);
+        assert_eq!(stats.reads, 1);
+        assert!(stats.ipc() > 3.);
+    }
+
+    #[test]
+    fn test_core_window_stall() {
+        // the loads to different rows of a bank fill the small window
+        let trace: String = (0..64)
+            .map(|i| format!("0 {:#x} {:#x}\n", i << 24, (i << 24) + 64))
+            .collect();
+        let config = CoreConfig {
+            window_size: 4,
+            ..Default::default()
+        };
+        let mut core = Core::new(config, CpuTraceReader::new(trace.as_bytes()));
+        let stats = run(&mut core, &mut new_memory()).unwrap();
+        assert_eq!(stats.instructions, 64);
+        assert_eq!(stats.writebacks, 64);
+        assert!(stats.window_full_cycles > 0);
+        assert!(stats.ipc() < 1.);
+    }
+
+    #[test]
+    fn test_run_cores() {
+        // a streaming core and a core hitting different rows of bank 0
+        let streaming: String = (0..64).map(|i| format!("2 {:#x}\n", i * 64)).collect();
+        let conflict: String = (1..65).map(|i| format!("2 {:#x}\n", i << 24)).collect();
+        let mut cores = vec![
+            Core::new(
+                Default::default(),
+                CpuTraceReader::new(streaming.as_bytes()),
+            ),
+            Core::new(Default::default(), CpuTraceReader::new(conflict.as_bytes())),
+        ];
+        let mut memory = new_memory();
+        let stats = run_cores(&mut cores, &mut memory).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|stats| stats.instructions == 192));
+        assert!(stats[0].ipc() > stats[1].ipc());
+
+        let sources = memory.source_stats();
+        assert_eq!(sources.len(), 2);
+        assert!(sources.iter().all(|source| source.reads == 64));
+        // the streaming core still hits its row between the conflicts
+        assert!(sources[0].row_hit_rate() > 0.5);
+        assert_eq!(sources[1].row_hits, 0);
+        assert!(sources.iter().all(|source| source.average_latency() > 36.));
+        let cycles = memory.get_cycle();
+        assert!(sources[0].bandwidth_gbps(cycles, memory.clk_ns()) > 0.);
+
+        let mut cores = vec![
+            Core::new(
+                Default::default(),
+                CpuTraceReader::new("0 0x0\n".as_bytes()),
+            ),
+            Core::new(
+                CoreConfig {
+                    frequency_ghz: 4.,
+                    ..Default::default()
+                },
+                CpuTraceReader::new("0 0x0\n".as_bytes()),
+            ),
+        ];
+        assert!(matches!(
+            run_cores(&mut cores, &mut new_memory()),
+            Err(Error::InvalidCore(_))
+        ));
+    }
+
+    #[test]
+    fn test_core_writeback_order() {
+        // the writeback of each entry follows its load, even with the bubbles before the load
+        let trace: String = (0..16)
+            .map(|i| format!("3 {:#x} {:#x}\n", i * 64, (i + 1) << 24))
+            .collect();
+        let mut core = Core::new(Default::default(), CpuTraceReader::new(trace.as_bytes()));
+        let mut memory = Recorder {
+            memory: new_memory(),
+            sent: vec![],
+        };
+        let stats = run(&mut core, &mut memory).unwrap();
+        assert_eq!(stats.writebacks, 16);
+        assert_eq!(memory.sent.len(), 32);
+        for (i, pair) in memory.sent.chunks(2).enumerate() {
+            assert_eq!(pair[0], (ReqType::Read, i as u64 * 64));
+            assert_eq!(pair[1], (ReqType::Write, (i as u64 + 1) << 24));
+        }
+    }
+
+    #[test]
+    fn test_invalid_core() {
+        for config in [
+            CoreConfig {
+                window_size: 0,
+                ..Default::default()
+            },
+            CoreConfig {
+                width: 0,
+                ..Default::default()
+            },
+            CoreConfig {
+                frequency_ghz: 0.,
+                ..Default::default()
+            },
+            CoreConfig {
+                frequency_ghz: f64::NAN,
+                ..Default::default()
+            },
+        ] {
+            let mut core = Core::new(config.clone(), CpuTraceReader::new("0 0x0\n".as_bytes()));
+            assert!(matches!(
+                run(&mut core, &mut new_memory()),
+                Err(Error::InvalidCore(_))
+            ));
+            let mut cores = vec![Core::new(config, CpuTraceReader::new("0 0x0\n".as_bytes()))];
+            assert!(matches!(
+                run_cores(&mut cores, &mut new_memory()),
+                Err(Error::InvalidCore(_))
+            ));
+        }
+    }
+}
@@ -4,14 +4,15 @@ use serde::{Deserialize, Serialize};
 use crate::{
     config::Config,
     dram::{self, CommandTrait, Dram, DramSpec, LevelTrait, State, TimeEntry},
+    error::{ConfigIssue, Error},
     memory::MappingType,
     request::ReqType,
-    utils::{self, clear_lower_bits},
+    utils::clear_lower_bits,
 };
 fn log2(mut x: usize) -> usize {
     let mut i = 0;
     while x > 1 {
-        x = x >> 1;
+        x >>= 1;
         i += 1;
     }
     i
@@ -125,7 +126,7 @@ impl CommandTrait for Command {
     }
 
     fn is_act(&self) -> bool {
-        return *self == Command::ACT;
+        *self == Command::ACT
     }
 }
 #[allow(non_camel_case_types)]
@@ -141,47 +142,261 @@ pub enum DDR4Org {
     DDR4_8Gb_x4,
     DDR4_8Gb_x8,
     DDR4_8Gb_x16,
+    DDR4_16Gb_x4,
+    DDR4_16Gb_x8,
+    DDR4_16Gb_x16,
     MAX,
 }
+impl DDR4Org {
+    /// the organization of the preset
+    pub fn organization(&self) -> Result<DDR4Organization, Error> {
+        let (bank_groups, rows, dq) = match self {
+            DDR4Org::DDR4_2Gb_x4 => (4, 1 << 15, 4),
+            DDR4Org::DDR4_2Gb_x8 => (4, 1 << 14, 8),
+            DDR4Org::DDR4_2Gb_x16 => (2, 1 << 14, 16),
+            DDR4Org::DDR4_4Gb_x4 => (4, 1 << 16, 4),
+            DDR4Org::DDR4_4Gb_x8 => (4, 1 << 15, 8),
+            DDR4Org::DDR4_4Gb_x16 => (2, 1 << 15, 16),
+            DDR4Org::DDR4_8Gb_x4 => (4, 1 << 17, 4),
+            DDR4Org::DDR4_8Gb_x8 => (4, 1 << 16, 8),
+            DDR4Org::DDR4_8Gb_x16 => (2, 1 << 16, 16),
+            DDR4Org::DDR4_16Gb_x4 => (4, 1 << 18, 4),
+            DDR4Org::DDR4_16Gb_x8 => (4, 1 << 17, 8),
+            DDR4Org::DDR4_16Gb_x16 => (2, 1 << 17, 16),
+            DDR4Org::MAX => {
+                return Err(Error::InvalidOrg(
+                    "`MAX` is not a DDR4 organization".to_string(),
+                ))
+            }
+        };
+        Ok(DDR4Organization {
+            bank_groups,
+            banks_per_group: 4,
+            rows,
+            columns: 1 << 10,
+            dq,
+        })
+    }
+}
+
+/// the organization of a DDR4 chip
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DDR4Organization {
+    pub bank_groups: usize,
+    pub banks_per_group: usize,
+    pub rows: usize,
+    /// the columns per row, each column is `dq` bits
+    pub columns: usize,
+    /// the data width of a chip
+    pub dq: usize,
+}
+impl DDR4Organization {
+    /// the density of a chip in bits
+    pub fn density(&self) -> usize {
+        self.bank_groups * self.banks_per_group * self.rows * self.columns * self.dq
+    }
+    /// the bytes of a row in a chip, 512B for x4, 1KB for x8 and 2KB for x16
+    pub fn page_size(&self) -> usize {
+        self.columns * self.dq / 8
+    }
+}
+
+/// the organization of DDR4 in config, either a preset or an explicit organization
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DDR4OrgConfig {
+    /// a JEDEC preset like `org = "DDR4_4Gb_x8"`
+    #[serde(rename = "org")]
+    Preset(DDR4Org),
+    /// an explicit `[dram.organization]` table
+    #[serde(rename = "organization")]
+    Custom(DDR4Organization),
+}
+impl DDR4OrgConfig {
+    pub fn organization(&self) -> Result<DDR4Organization, Error> {
+        match self {
+            DDR4OrgConfig::Preset(org) => org.organization(),
+            DDR4OrgConfig::Custom(organization) => {
+                for (name, size) in [
+                    ("bank_groups", organization.bank_groups),
+                    ("banks_per_group", organization.banks_per_group),
+                    ("rows", organization.rows),
+                    ("columns", organization.columns),
+                ] {
+                    if !size.is_power_of_two() {
+                        return Err(Error::InvalidOrg(format!(
+                            "`{}` should be a power of two, got {}",
+                            name, size
+                        )));
+                    }
+                }
+                // the chips of a rank should fill the channel width
+                if ![4, 8, 16].contains(&organization.dq) {
+                    return Err(Error::InvalidOrg(format!(
+                        "`dq` should be 4, 8 or 16, got {}",
+                        organization.dq
+                    )));
+                }
+                Ok(organization.clone())
+            }
+        }
+    }
+}
+
+/// the DDR4 specific part of the config
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DDR4Config {
+    #[serde(flatten)]
+    pub org: DDR4OrgConfig,
+    pub speed: Speed,
+}
+impl Default for DDR4Config {
+    fn default() -> Self {
+        Self {
+            org: DDR4OrgConfig::Preset(DDR4Org::DDR4_4Gb_x8),
+            speed: Speed::DDR4_2400R,
+        }
+    }
+}
+impl DDR4Config {
+    /// the DDR4 part of `Config::validate`
+    pub(crate) fn validate(&self, config: &Config, issues: &mut Vec<ConfigIssue>) {
+        let org_field = match self.org {
+            DDR4OrgConfig::Preset(_) => "dram.org",
+            DDR4OrgConfig::Custom(_) => "dram.organization",
+        };
+        let org = match self.org.organization() {
+            Ok(org) => Some(org),
+            Err(e) => {
+                issues.push(ConfigIssue::new(org_field, e.to_string()));
+                None
+            }
+        };
+        // only the names of the timings are checked here, any organization will do
+        let any_org = DDR4Org::DDR4_8Gb_x8.organization().unwrap();
+        let mut speed_entry = DDR4::get_speed(&self.speed, org.as_ref().unwrap_or(&any_org));
+        for (name, value) in config.timing.iter() {
+            if let Err(e) = speed_entry.set_timing(name, *value) {
+                issues.push(ConfigIssue::new(format!("timing.{}", name), e));
+            }
+        }
+        let Some(org) = org else {
+            return;
+        };
+        // x16 devices only come in 1 or 2 rank modules
+        let max_ranks = if org.dq == 16 { 2 } else { 4 };
+        if config.ranks > max_ranks {
+            issues.push(ConfigIssue::new(
+                "ranks",
+                format!(
+                    "at most {} ranks with x{} devices, got {}",
+                    max_ranks, org.dq, config.ranks
+                ),
+            ));
+        }
+        issues.extend(DDR4::request_size_issue(config, &org));
+        // the mapping can only be checked when all the sizes are valid
+        if issues.is_empty() {
+            let (addr_size, _) = DDR4::get_addr_size(config, &org);
+            let addr_bits = addr_size.iter().map(|x| log2(*x)).collect::<Vec<usize>>();
+            if let Err(e) = config
+                .mapping_type
+                .check(&addr_bits, Level::have_bank_group())
+            {
+                issues.push(ConfigIssue::new("mapping_type", e));
+            }
+        }
+    }
+}
 
-#[allow(non_snake_case, dead_code)]
+#[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize)]
 
 pub struct SpeedEntry {
-    rate: u64,
-    freq: f64,
-    tCK: f64,
-    nBL: u64,
-    nCCDS: u64,
-    nCCDL: u64,
-    nRTRS: u64,
-    nCL: u64,
-    nRCD: u64,
-    nRP: u64,
-    nCWL: u64,
-    nRAS: u64,
-    nRC: u64,
-    nRTP: u64,
-    nWTRS: u64,
-    nWTRL: u64,
-    nWR: u64,
-    nRRDS: u64,
-    nRRDL: u64,
-    nFAW: u64,
-    nRFC: u64,
-    nREFI: u64,
-    nPD: u64,
-    nXP: u64,
-    nXPDLL: u64,
-    nCKESR: u64,
-    nXS: u64,
-    nXSDLL: u64,
+    pub rate: u64,
+    pub freq: f64,
+    pub tCK: f64,
+    pub nBL: u64,
+    pub nCCDS: u64,
+    pub nCCDL: u64,
+    pub nRTRS: u64,
+    pub nCL: u64,
+    pub nRCD: u64,
+    pub nRP: u64,
+    pub nCWL: u64,
+    pub nRAS: u64,
+    pub nRC: u64,
+    pub nRTP: u64,
+    pub nWTRS: u64,
+    pub nWTRL: u64,
+    pub nWR: u64,
+    pub nRRDS: u64,
+    pub nRRDL: u64,
+    pub nFAW: u64,
+    pub nRFC: u64,
+    pub nREFI: u64,
+    pub nPD: u64,
+    pub nXP: u64,
+    pub nXPDLL: u64,
+    pub nCKESR: u64,
+    pub nXS: u64,
+    pub nXSDLL: u64,
+}
+/// generate the setter that override a field of `SpeedEntry` by its name
+macro_rules! set_speed_entry_field {
+    ($entry:expr, $name:expr, $value:expr; cycles: $($cycle:ident),*; float: $($float:ident),*) => {
+        match $name {
+            $(stringify!($cycle) => {
+                if $value < 0.0 || $value.fract() != 0.0 {
+                    return Err(format!(
+                        "timing `{}` should be a non-negative integer number of cycles, got {}",
+                        $name, $value
+                    ));
+                }
+                $entry.$cycle = $value as u64;
+            })*
+            $(stringify!($float) => {
+                if $value <= 0.0 {
+                    return Err(format!("timing `{}` should be positive, got {}", $name, $value));
+                }
+                $entry.$float = $value;
+            })*
+            _ => {
+                return Err(format!(
+                    "unknown timing `{}`, valid timings are: {}",
+                    $name,
+                    [$(stringify!($cycle)),*, $(stringify!($float)),*].join(", ")
+                ))
+            }
+        }
+    };
+}
+impl SpeedEntry {
+    /// override a single timing by the field name, like `nRCD` or `tCK`
+    /// - the `n*` timings and `rate` are in cycles and should be integers
+    pub fn set_timing(&mut self, name: &str, value: f64) -> Result<(), String> {
+        set_speed_entry_field!(self, name, value;
+            cycles: rate, nBL, nCCDS, nCCDL, nRTRS, nCL, nRCD, nRP, nCWL, nRAS, nRC, nRTP, nWTRS,
+                nWTRL, nWR, nRRDS, nRRDL, nFAW, nRFC, nREFI, nPD, nXP, nXPDLL, nCKESR, nXS, nXSDLL;
+            float: freq, tCK);
+        Ok(())
+    }
 }
+/// the data width of a DDR4 channel in bits
+const CHANNEL_WIDTH: usize = 64;
+/// the burst length of DDR4
+const PREFETCH_SIZE: usize = 8;
+
 pub struct DDR4 {
     addr_size: Vec<usize>,
     addr_bits: Vec<usize>,
     timing: Vec<Vec<Vec<TimeEntry<Command>>>>,
     read_latency: u64,
+    write_latency: u64,
+    tck_ns: f64,
+    /// the addr bits inside a request, which are ignored by the mapping
+    offset_bits: usize,
+    /// the number of bursts to transfer a request
+    request_bursts: usize,
 }
 #[derive(Debug, Serialize, Deserialize)]
 
@@ -194,31 +409,201 @@ pub enum Speed {
     DDR4_2133R,
     DDR4_2400R,
     DDR4_2400U,
+    DDR4_2666T,
+    DDR4_2666U,
+    DDR4_2666V,
+    DDR4_2666W,
+    DDR4_2933V,
+    DDR4_2933W,
+    DDR4_2933Y,
+    DDR4_2933AA,
+    /// the 22-22-22 bin used by ramulator, the timings are hand-computed cycles
     DDR4_3200,
+    DDR4_3200W,
+    DDR4_3200AA,
+    DDR4_3200AC,
+}
+
+/// convert a timing in ns to cycles with the JEDEC rounding algorithm,
+/// which tolerates 2.5% before rounding up to the next cycle
+pub fn ns_to_cycles(t_ns: f64, tck_ns: f64) -> u64 {
+    let t_ps = (t_ns * 1000.0).round() as u64;
+    let tck_ps = (tck_ns * 1000.0).round() as u64;
+    (t_ps * 1000 / tck_ps + 974) / 1000
+}
+
+/// the JEDEC tRFC1 in ns of a chip density in bits
+pub fn trfc_ns(density: usize) -> f64 {
+    match density >> 30 {
+        0..=2 => 160.0,
+        4 => 260.0,
+        8 => 350.0,
+        _ => 550.0,
+    }
+}
+
+/// a speed bin defined in nanoseconds, the timings that are not listed here
+/// are derived from the JEDEC `max(n nCK, t ns)` rules in `to_speed_entry`
+/// - `tRRDS`, `tRRDL` and `tFAW` are for the 1KB page size, see `with_page_size`
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedBinNs {
+    pub rate: u64,
+    pub tCK: f64,
+    pub nCWL: u64,
+    pub tCL: f64,
+    pub tRCD: f64,
+    pub tRP: f64,
+    pub tRAS: f64,
+    pub tCCDL: f64,
+    pub tRRDS: f64,
+    pub tRRDL: f64,
+    pub tFAW: f64,
+    /// the minimum nFAW in cycles
+    pub nFAW: u64,
+    pub tRFC: f64,
+    pub tREFI: f64,
+    /// the DLL lock time in cycles
+    pub nDLLK: u64,
+}
+#[allow(non_snake_case)]
+impl SpeedBinNs {
+    /// convert the bin to cycles
+    pub fn to_speed_entry(&self) -> SpeedEntry {
+        let n = |t_ns: f64, min_cycles: u64| ns_to_cycles(t_ns, self.tCK).max(min_cycles);
+        let nPD = n(5.0, 3);
+        SpeedEntry {
+            rate: self.rate,
+            freq: 1000.0 / self.tCK,
+            tCK: self.tCK,
+            nBL: 4,
+            nCCDS: 4,
+            nCCDL: n(self.tCCDL, 5),
+            nRTRS: 2,
+            nCL: n(self.tCL, 0),
+            nRCD: n(self.tRCD, 0),
+            nRP: n(self.tRP, 0),
+            nCWL: self.nCWL,
+            nRAS: n(self.tRAS, 0),
+            nRC: n(self.tRAS + self.tRP, 0),
+            nRTP: n(7.5, 4),
+            nWTRS: n(2.5, 2),
+            nWTRL: n(7.5, 4),
+            nWR: n(15.0, 0),
+            nRRDS: n(self.tRRDS, 4),
+            nRRDL: n(self.tRRDL, 4),
+            nFAW: n(self.tFAW, self.nFAW),
+            nRFC: n(self.tRFC, 0),
+            nREFI: n(self.tREFI, 0),
+            nPD,
+            nXP: n(6.0, 4),
+            nXPDLL: n(24.0, 10),
+            nCKESR: nPD + 1,
+            nXS: n(self.tRFC + 10.0, 0),
+            nXSDLL: self.nDLLK,
+        }
+    }
+    /// a DDR4-2666 bin with `tCL = tRCD = tRP = tAA`, tRFC is for the chip density in bits
+    fn ddr4_2666(tAA: f64, density: usize) -> Self {
+        Self {
+            rate: 2666,
+            tCK: 0.75,
+            nCWL: 14,
+            tCL: tAA,
+            tRCD: tAA,
+            tRP: tAA,
+            tRAS: 32.0,
+            tCCDL: 5.0,
+            tRRDS: 3.0,
+            tRRDL: 4.9,
+            tFAW: 21.0,
+            nFAW: 20,
+            tRFC: trfc_ns(density),
+            tREFI: 7800.0,
+            nDLLK: 854,
+        }
+    }
+    /// a DDR4-2933 bin with `tCL = tRCD = tRP = tAA`, tRFC is for the chip density in bits
+    fn ddr4_2933(tAA: f64, density: usize) -> Self {
+        Self {
+            rate: 2933,
+            tCK: 0.682,
+            nCWL: 16,
+            tRRDS: 2.7,
+            nDLLK: 940,
+            ..Self::ddr4_2666(tAA, density)
+        }
+    }
+    /// a DDR4-3200 bin with `tCL = tRCD = tRP = tAA`, tRFC is for the chip density in bits
+    fn ddr4_3200(tAA: f64, density: usize) -> Self {
+        Self {
+            rate: 3200,
+            tCK: 0.625,
+            nCWL: 16,
+            tRRDS: 2.5,
+            nDLLK: 1024,
+            ..Self::ddr4_2666(tAA, density)
+        }
+    }
+    /// the activate timings of a page size in bytes, the x4 parts have 512B pages
+    /// and a shorter tFAW, the x16 parts have 2KB pages and longer timings
+    fn with_page_size(self, page_size: usize) -> Self {
+        match page_size {
+            ..=512 => Self {
+                tFAW: match self.rate {
+                    2666 => 12.0,
+                    2933 => 10.875,
+                    _ => 10.0,
+                },
+                nFAW: 16,
+                ..self
+            },
+            1024 => self,
+            _ => Self {
+                tRRDS: 5.3,
+                tRRDL: 6.4,
+                tFAW: 30.0,
+                nFAW: 28,
+                ..self
+            },
+        }
+    }
 }
 impl DDR4 {
-    pub fn new(config: &Config) -> Self {
+    pub fn new(config: &Config) -> Result<Self, Error> {
         tracing::info!("building ddr4");
-        let channels = config.channels;
-        let ranks = config.ranks;
-
-        // not this is different than the original code, the col = origin_col -3 because we substracted the 3 bit for burst length of 8
-        let addr_size = match config.ddr4_org {
-            DDR4Org::DDR4_2Gb_x4 => vec![channels, ranks, 4, 4, 1 << 15, 1 << 7],
-            DDR4Org::DDR4_2Gb_x8 => vec![channels, ranks, 4, 4, 1 << 14, 1 << 7],
-            DDR4Org::DDR4_2Gb_x16 => vec![channels, ranks, 2, 4, 1 << 14, 1 << 7],
-            DDR4Org::DDR4_4Gb_x4 => vec![channels, ranks, 4, 4, 1 << 16, 1 << 7],
-            DDR4Org::DDR4_4Gb_x8 => vec![channels, ranks, 4, 4, 1 << 15, 1 << 7],
-            DDR4Org::DDR4_4Gb_x16 => vec![channels, ranks, 2, 4, 1 << 15, 1 << 7],
-            DDR4Org::DDR4_8Gb_x4 => vec![channels, ranks, 4, 4, 1 << 17, 1 << 7],
-            DDR4Org::DDR4_8Gb_x8 => vec![channels, ranks, 4, 4, 1 << 16, 1 << 7],
-            DDR4Org::DDR4_8Gb_x16 => vec![channels, ranks, 2, 4, 1 << 16, 1 << 7],
-            DDR4Org::MAX => unreachable!(),
-        };
+        let ddr4_config = config.dram.as_ddr4().ok_or(Error::WrongStandard {
+            expected: "DDR4",
+            found: config.dram.standard(),
+        })?;
+
+        let org = ddr4_config.org.organization()?;
+        tracing::info!(?org, density = org.density(), "organization");
+        // a request smaller than a burst still takes a whole burst, a larger one takes several bursts
+        let burst_size = CHANNEL_WIDTH * PREFETCH_SIZE / 8;
+        if let Some(issue) = config
+            .request_size_issue()
+            .or_else(|| Self::request_size_issue(config, &org))
+        {
+            return Err(Error::InvalidConfig(vec![issue]));
+        }
+        let (addr_size, request_bursts) = Self::get_addr_size(config, &org);
+        let offset_bits = log2(burst_size * request_bursts);
+        tracing::info!(burst_size, request_bursts, offset_bits, "request size");
         tracing::info!(?addr_size, "addr_size");
         let addr_bits = addr_size.iter().map(|x| log2(*x)).collect::<Vec<usize>>();
         tracing::info!(?addr_bits, "addr_bits");
-        let speed_entry = Self::get_speed(&config.ddr4_speed);
+        config
+            .mapping_type
+            .check(&addr_bits, Level::have_bank_group())
+            .map_err(Error::InvalidMapping)?;
+        let mut speed_entry = Self::get_speed(&ddr4_config.speed, &org);
+        for (name, value) in config.timing.iter() {
+            tracing::info!(name, value, "override timing");
+            speed_entry
+                .set_timing(name, *value)
+                .map_err(Error::InvalidSpeed)?;
+        }
         tracing::info!(?speed_entry, "speed_entry");
         let mut timing = vec![vec![vec![]; Command::MAX]; Level::MAX_LEVEL];
         Self::init_timing(&mut timing, &speed_entry);
@@ -231,26 +616,60 @@ impl DDR4 {
         }
         let read_latency = speed_entry.nCL + speed_entry.nBL;
         tracing::info!(?read_latency, "read_latency");
-        Self {
+        let write_latency = speed_entry.nCWL + speed_entry.nBL;
+        tracing::info!(?write_latency, "write_latency");
+        Ok(Self {
             addr_size,
             addr_bits,
             timing,
             read_latency,
-        }
+            write_latency,
+            tck_ns: speed_entry.tCK,
+            offset_bits,
+            request_bursts,
+        })
+    }
+
+    /// a request larger than a row leaves no column in the addr
+    fn request_size_issue(config: &Config, org: &DDR4Organization) -> Option<ConfigIssue> {
+        let burst_size = CHANNEL_WIDTH * PREFETCH_SIZE / 8;
+        (config.request_size > burst_size * org.columns / PREFETCH_SIZE).then(|| {
+            ConfigIssue::new(
+                "request_size",
+                format!("{} is larger than a row", config.request_size),
+            )
+        })
+    }
+
+    /// the size of each level and the bursts of a request
+    fn get_addr_size(config: &Config, org: &DDR4Organization) -> (Vec<usize>, usize) {
+        let burst_size = CHANNEL_WIDTH * PREFETCH_SIZE / 8;
+        let request_bursts = (config.request_size / burst_size).max(1);
+        // not this is different than the original code, the col = origin_col -3 because we substracted the 3 bit for burst length of 8,
+        // and the bits of the bursts in a request
+        let addr_size = vec![
+            config.channels,
+            config.ranks,
+            org.bank_groups,
+            org.banks_per_group,
+            org.rows,
+            org.columns / PREFETCH_SIZE / request_bursts,
+        ];
+        (addr_size, request_bursts)
     }
 
     pub fn addr_from_addr_vec(&self, addr_vec: &[u64]) -> u64 {
         assert!(addr_vec.len() == self.addr_bits.len());
         let mut addr = 0;
-        for i in 0..addr_vec.len() {
-            addr <<= self.addr_bits[i];
-            addr += addr_vec[i];
+        for (bits, level_addr) in self.addr_bits.iter().zip(addr_vec) {
+            addr <<= bits;
+            addr += level_addr;
         }
-        addr <<= 6;
+        addr <<= self.offset_bits;
         addr
     }
 
-    fn init_timing(timing: &mut Vec<Vec<Vec<TimeEntry<Command>>>>, s: &SpeedEntry) {
+    fn init_timing(timing: &mut [Vec<Vec<TimeEntry<Command>>>], s: &SpeedEntry) {
         /*** Channel ***/
         let t = &mut timing[Level::Channel as usize];
 
@@ -910,7 +1329,11 @@ impl DDR4 {
         });
     }
 
-    pub fn get_speed(speed: &Speed) -> SpeedEntry {
+    /// the timings of the speed bin, the bins in ns take the refresh timings from the chip
+    /// density and the activate timings from the page size
+    pub fn get_speed(speed: &Speed, org: &DDR4Organization) -> SpeedEntry {
+        let density = org.density();
+        let ns_bin = |bin: SpeedBinNs| bin.with_page_size(org.page_size()).to_speed_entry();
         match speed {
             Speed::DDR4_1600K => SpeedEntry {
                 rate: 1600,
@@ -1182,6 +1605,17 @@ impl DDR4 {
                 nXS: 0,
                 nXSDLL: 0,
             },
+            Speed::DDR4_2666T => ns_bin(SpeedBinNs::ddr4_2666(12.75, density)),
+            Speed::DDR4_2666U => ns_bin(SpeedBinNs::ddr4_2666(13.50, density)),
+            Speed::DDR4_2666V => ns_bin(SpeedBinNs::ddr4_2666(14.25, density)),
+            Speed::DDR4_2666W => ns_bin(SpeedBinNs::ddr4_2666(15.00, density)),
+            Speed::DDR4_2933V => ns_bin(SpeedBinNs::ddr4_2933(13.64, density)),
+            Speed::DDR4_2933W => ns_bin(SpeedBinNs::ddr4_2933(14.32, density)),
+            Speed::DDR4_2933Y => ns_bin(SpeedBinNs::ddr4_2933(15.00, density)),
+            Speed::DDR4_2933AA => ns_bin(SpeedBinNs::ddr4_2933(16.37, density)),
+            Speed::DDR4_3200W => ns_bin(SpeedBinNs::ddr4_3200(12.50, density)),
+            Speed::DDR4_3200AA => ns_bin(SpeedBinNs::ddr4_3200(13.75, density)),
+            Speed::DDR4_3200AC => ns_bin(SpeedBinNs::ddr4_3200(15.00, density)),
         }
     }
 }
@@ -1316,39 +1750,38 @@ impl DramSpec for DDR4 {
         }
     }
     fn get_timming(&self, level: &Level, cmd: &Command) -> &[TimeEntry<Self::Command>] {
-        return &self.timing[*level as usize][*cmd as usize];
+        &self.timing[*level as usize][*cmd as usize]
     }
 
     fn get_read_latency(&self) -> u64 {
-        return self.read_latency;
+        self.read_latency
+    }
+
+    fn get_write_latency(&self) -> u64 {
+        self.write_latency
+    }
+
+    fn get_tck_ns(&self) -> f64 {
+        self.tck_ns
     }
 
     fn get_addr_bits(&self, level: &Self::Level) -> usize {
-        return self.addr_bits[level.to_usize()];
+        self.addr_bits[level.to_usize()]
     }
 
     fn get_addr_size(&self, level: &Self::Level) -> usize {
-        return self.addr_size[level.to_usize()];
+        self.addr_size[level.to_usize()]
     }
 
     fn decode_addr(&self, mut addr: u64, mapping_type: &MappingType) -> Vec<u64> {
-        clear_lower_bits(&mut addr, 6);
+        clear_lower_bits(&mut addr, self.offset_bits);
         let mut addr_vec = vec![0; Level::MAX_LEVEL];
-        utils::setup_addr_vec(
-            addr,
-            self.get_full_addr_bits(),
-            &mut addr_vec,
-            mapping_type.get_slice_sequence(true),
-        );
+        mapping_type.setup_addr_vec(addr, self.get_full_addr_bits(), &mut addr_vec, true);
         addr_vec
     }
-    /// return the addr represented by the addr_vec, note: the lower 6 bits are ignored
+    /// return the addr represented by the addr_vec, note: the offset bits are ignored
     fn encode_addr(&self, addr: &[u64], mapping_type: &MappingType) -> u64 {
-        utils::set_up_addr(
-            addr,
-            self.get_full_addr_bits(),
-            mapping_type.get_slice_sequence(true),
-        ) << 6
+        mapping_type.set_up_addr(addr, self.get_full_addr_bits(), true) << self.offset_bits
     }
 
     fn get_full_addr_bits(&self) -> &[usize] {
@@ -1360,24 +1793,45 @@ impl DramSpec for DDR4 {
     }
 
     fn get_prefetch_size(&self) -> usize {
-        8
+        PREFETCH_SIZE
     }
 
     fn get_channel_width(&self) -> usize {
-        64
+        CHANNEL_WIDTH
+    }
+
+    fn get_offset_bits(&self) -> usize {
+        self.offset_bits
+    }
+
+    fn get_request_bursts(&self) -> usize {
+        self.request_bursts
+    }
+
+    fn get_cmd_bus_num(&self) -> usize {
+        1
+    }
+
+    fn get_cmd_bus(&self, _cmd: &Command) -> usize {
+        // row and column commands share the same bus
+        0
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::init_logger;
+    use crate::{
+        config::DramConfig,
+        init_logger,
+        memory::{BitMapping, MappingLevel, XorHash, XorMapping},
+    };
 
     use super::*;
     #[test]
     fn test_address() {
         init_logger();
         let config = Config::default();
-        let ddr4 = DDR4::new(&config);
+        let ddr4 = DDR4::new(&config).unwrap();
         let span = tracing::span!(tracing::Level::DEBUG, "testing ddr4 address");
         let _enter = span.enter();
         let addr = 1;
@@ -1407,4 +1861,320 @@ mod tests {
         tracing::debug!("{:?}", addr_);
         assert_eq!(addr, addr_ + 1);
     }
+
+    #[test]
+    fn test_timing_override() {
+        let mut config = Config::default();
+        config.timing.insert("nRCD".to_string(), 20.);
+        config.timing.insert("nFAW".to_string(), 30.);
+        let ddr4 = DDR4::new(&config).unwrap();
+        let act_to_rd = ddr4
+            .get_timming(&Level::Bank, &Command::ACT)
+            .iter()
+            .find(|entry| entry.cmd == Command::RD)
+            .unwrap();
+        assert_eq!(act_to_rd.val, 20);
+        let act_to_act = ddr4
+            .get_timming(&Level::Rank, &Command::ACT)
+            .iter()
+            .find(|entry| entry.cmd == Command::ACT && entry.dist == 4)
+            .unwrap();
+        assert_eq!(act_to_act.val, 30);
+    }
+
+    #[test]
+    fn test_custom_organization() {
+        let config = Config {
+            dram: DramConfig::DDR4(DDR4Config {
+                org: DDR4OrgConfig::Custom(DDR4Organization {
+                    bank_groups: 2,
+                    banks_per_group: 2,
+                    rows: 1 << 12,
+                    columns: 1 << 9,
+                    dq: 8,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        assert_eq!(ddr4.get_full_addr_size(), &[1, 1, 2, 2, 1 << 12, 1 << 6]);
+        assert_eq!(ddr4.get_full_addr_bits(), &[0, 0, 1, 1, 12, 6]);
+    }
+
+    #[test]
+    fn test_16gb_presets() {
+        for org in [
+            DDR4Org::DDR4_16Gb_x4,
+            DDR4Org::DDR4_16Gb_x8,
+            DDR4Org::DDR4_16Gb_x16,
+        ] {
+            assert_eq!(org.organization().unwrap().density(), 16 << 30);
+        }
+        assert_eq!(
+            DDR4Org::DDR4_8Gb_x8.organization().unwrap().density(),
+            8 << 30
+        );
+        // the refresh takes tRFC1 of the density, 260ns, 350ns and 550ns at 0.625ns,
+        // and tXS is 10ns longer
+        for (org, n_rfc, n_xs) in [
+            (DDR4Org::DDR4_4Gb_x8, 416, 432),
+            (DDR4Org::DDR4_8Gb_x8, 560, 576),
+            (DDR4Org::DDR4_16Gb_x8, 880, 896),
+        ] {
+            let entry = DDR4::get_speed(&Speed::DDR4_3200AA, &org.organization().unwrap());
+            assert_eq!((entry.nRFC, entry.nXS), (n_rfc, n_xs));
+            let config = Config {
+                dram: DramConfig::DDR4(DDR4Config {
+                    org: DDR4OrgConfig::Preset(org),
+                    speed: Speed::DDR4_3200AA,
+                }),
+                ..Default::default()
+            };
+            let ddr4 = DDR4::new(&config).unwrap();
+            let refresh = &ddr4.timing[Level::Rank as usize][Command::REF as usize];
+            let ref_to_act = refresh.iter().find(|t| t.cmd == Command::ACT).unwrap();
+            assert_eq!(ref_to_act.val, n_rfc);
+        }
+    }
+
+    #[test]
+    fn test_ns_speed_bin() {
+        let org = DDR4Org::DDR4_8Gb_x8.organization().unwrap();
+        assert_eq!(ns_to_cycles(13.75, 0.625), 22);
+        assert_eq!(ns_to_cycles(13.32, 0.833), 16);
+        assert_eq!(ns_to_cycles(16.37, 0.682), 24);
+        // the 2400R bin in ns should match the hand-computed cycles
+        let from_ns = SpeedBinNs {
+            rate: 2400,
+            tCK: 0.833,
+            nCWL: 12,
+            tCL: 13.32,
+            tRCD: 13.32,
+            tRP: 13.32,
+            tRAS: 32.0,
+            tCCDL: 5.0,
+            tRRDS: 3.3,
+            tRRDL: 4.9,
+            tFAW: 21.0,
+            nFAW: 20,
+            tRFC: 350.0,
+            tREFI: 7800.0,
+            nDLLK: 768,
+        }
+        .to_speed_entry();
+        let table = DDR4::get_speed(&Speed::DDR4_2400R, &org);
+        assert_eq!(
+            [
+                from_ns.nCL,
+                from_ns.nRCD,
+                from_ns.nRP,
+                from_ns.nRAS,
+                from_ns.nRC,
+                from_ns.nCCDL,
+                from_ns.nRTP,
+                from_ns.nWTRS,
+                from_ns.nWTRL,
+                from_ns.nWR,
+                from_ns.nPD,
+                from_ns.nXP,
+                from_ns.nCKESR
+            ],
+            [
+                table.nCL,
+                table.nRCD,
+                table.nRP,
+                table.nRAS,
+                table.nRC,
+                table.nCCDL,
+                table.nRTP,
+                table.nWTRS,
+                table.nWTRL,
+                table.nWR,
+                table.nPD,
+                table.nXP,
+                table.nCKESR
+            ]
+        );
+        let entry = DDR4::get_speed(&Speed::DDR4_2666V, &org);
+        assert_eq!((entry.nCL, entry.nRCD, entry.nRP), (19, 19, 19));
+        let entry = DDR4::get_speed(&Speed::DDR4_2933Y, &org);
+        assert_eq!((entry.nCL, entry.nRAS), (22, 47));
+        let entry = DDR4::get_speed(&Speed::DDR4_3200AA, &org);
+        assert_eq!((entry.nCL, entry.nRC, entry.nFAW), (22, 74, 34));
+        // the activate timings of the 512B, 1KB and 2KB pages
+        for (org, n_rrds, n_rrdl, n_faw) in [
+            (DDR4Org::DDR4_8Gb_x4, 4, 8, 16),
+            (DDR4Org::DDR4_8Gb_x8, 4, 8, 34),
+            (DDR4Org::DDR4_8Gb_x16, 9, 11, 48),
+        ] {
+            let entry = DDR4::get_speed(&Speed::DDR4_3200AA, &org.organization().unwrap());
+            assert_eq!(
+                (entry.nRRDS, entry.nRRDL, entry.nFAW),
+                (n_rrds, n_rrdl, n_faw)
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn test_bit_mapping() {
+        init_logger();
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        // the same order as RoBaRaCoCh
+        let whole_levels = MappingType::Bits(BitMapping::new("Ro:15 Bg:2 Ba:2 Ra:0 Co:7").unwrap());
+        for addr in [0, 64, 64 * 4 + 1, 0x1234_5678, 0xdead_bec0] {
+            assert_eq!(
+                ddr4.decode_addr(addr, &whole_levels),
+                ddr4.decode_addr(addr, &MappingType::RoBaRaCoCh)
+            );
+        }
+        // split the column and the bank group around the bank
+        let split = MappingType::Bits(BitMapping::new("Ro:15 Bg Ba:2 Co:4 Bg Co Co Co").unwrap());
+        split.check(ddr4.get_full_addr_bits(), true).unwrap();
+        let addr_vec = ddr4.decode_addr(0b1_01_1010_0_111 << 6, &split);
+        assert_eq!(addr_vec, vec![0, 0, 0b10, 0b01, 0, 0b1010_111]);
+        for addr in [0, 64, 0x1234_5640, 0xdead_bec0] {
+            let addr_vec = ddr4.decode_addr(addr, &split);
+            assert_eq!(ddr4.encode_addr(&addr_vec, &split), addr);
+        }
+        // the column is one bit short
+        let short = MappingType::Bits(BitMapping::new("Ro:15 Bg:2 Ba:2 Co:6").unwrap());
+        assert!(short.check(ddr4.get_full_addr_bits(), true).is_err());
+        assert!(BitMapping::new("Ro:15 Xx:2").is_err());
+    }
+
+    #[test]
+    fn test_xor_mapping() {
+        init_logger();
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        // RoBaRaCoCh: column is bit 0-6, bank is bit 7-8, bank group is bit 9-10, row starts at bit 11
+        let xor = MappingType::Xor(XorMapping {
+            base: Box::new(MappingType::RoBaRaCoCh),
+            hashes: vec![XorHash {
+                level: MappingLevel::Ba,
+                masks: vec![1 << 11, 1 << 12],
+            }],
+        });
+        xor.check(ddr4.get_full_addr_bits(), true).unwrap();
+        let row_0 = ddr4.decode_addr(0, &xor);
+        let row_1 = ddr4.decode_addr(1 << (11 + 6), &xor);
+        assert_eq!(row_0[Level::Bank as usize], 0);
+        assert_eq!(row_1[Level::Bank as usize], 1);
+        for addr in [0, 64, 1 << 17, 0x1234_5640, 0x3ead_bec0] {
+            let addr_vec = ddr4.decode_addr(addr, &xor);
+            assert_eq!(ddr4.encode_addr(&addr_vec, &xor), addr);
+        }
+        // the mask selects a bank bit, which can not be inverted
+        let overlapped = MappingType::Xor(XorMapping {
+            base: Box::new(MappingType::RoBaRaCoCh),
+            hashes: vec![XorHash {
+                level: MappingLevel::Ba,
+                masks: vec![1 << 8 | 1 << 11],
+            }],
+        });
+        assert!(overlapped.check(ddr4.get_full_addr_bits(), true).is_err());
+    }
+
+    #[test]
+    fn test_request_size() {
+        init_logger();
+        // a 32B sector shares the burst with its neighbour
+        let config = Config {
+            request_size: 32,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        assert_eq!(ddr4.get_request_bursts(), 1);
+        assert_eq!(
+            ddr4.decode_addr(32, &MappingType::RoBaRaCoCh),
+            ddr4.decode_addr(0, &MappingType::RoBaRaCoCh)
+        );
+        assert_eq!(ddr4.decode_addr(64, &MappingType::RoBaRaCoCh)[5], 1);
+
+        // a 128B request takes two bursts, so there is one less column bit
+        let config = Config {
+            request_size: 128,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        assert_eq!(ddr4.get_request_bursts(), 2);
+        assert_eq!(ddr4.get_offset_bits(), 7);
+        assert_eq!(ddr4.get_addr_size(&Level::Column), 1 << 6);
+        assert_eq!(
+            ddr4.decode_addr(64, &MappingType::RoBaRaCoCh),
+            ddr4.decode_addr(0, &MappingType::RoBaRaCoCh)
+        );
+        let addr_vec = ddr4.decode_addr(128 * 3, &MappingType::RoBaRaCoCh);
+        assert_eq!(addr_vec[5], 3);
+        assert_eq!(
+            ddr4.encode_addr(&addr_vec, &MappingType::RoBaRaCoCh),
+            128 * 3
+        );
+    }
+
+    #[test]
+    fn test_invalid_timing_override() {
+        let mut entry = DDR4::get_speed(
+            &Speed::DDR4_2400R,
+            &DDR4Org::DDR4_8Gb_x8.organization().unwrap(),
+        );
+        assert!(entry.set_timing("tRCD", 16.).is_err());
+        assert!(entry.set_timing("nRCD", 16.5).is_err());
+        assert!(entry.set_timing("tCK", 0.).is_err());
+        assert!(entry.set_timing("tCK", 0.75).is_ok());
+    }
+
+    #[test]
+    fn test_new_errors() {
+        let mut config = Config::default();
+        config.timing.insert("nFOO".to_string(), 1.);
+        assert!(matches!(DDR4::new(&config), Err(Error::InvalidSpeed(_))));
+
+        let config = Config {
+            dram: DramConfig::DDR4(DDR4Config {
+                org: DDR4OrgConfig::Preset(DDR4Org::MAX),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(DDR4::new(&config), Err(Error::InvalidOrg(_))));
+
+        let config = Config {
+            dram: DramConfig::DDR4(DDR4Config {
+                org: DDR4OrgConfig::Custom(DDR4Organization {
+                    bank_groups: 4,
+                    banks_per_group: 4,
+                    rows: 3 << 14,
+                    columns: 1 << 10,
+                    dq: 8,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(DDR4::new(&config), Err(Error::InvalidOrg(_))));
+
+        let config = Config {
+            request_size: 96,
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = DDR4::new(&config) else {
+            panic!("the request size should be invalid");
+        };
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].field, "request_size");
+
+        // a row of 1024 columns is 8KB
+        let config = Config {
+            request_size: 16 << 10,
+            ..Default::default()
+        };
+        let Err(Error::InvalidConfig(issues)) = DDR4::new(&config) else {
+            panic!("the request size should be larger than a row");
+        };
+        assert_eq!(issues[0].field, "request_size");
+    }
 }
@@ -27,6 +27,7 @@ pub trait LevelTrait: Sized + Debug + PartialEq + Eq + Clone + Copy {
     fn is_channel(&self) -> bool;
     fn to_usize(&self) -> usize;
 }
+#[allow(clippy::result_unit_err)]
 pub trait CommandTrait: Sized + Debug + Clone + Copy + PartialEq + Eq {
     const MAX: usize;
     fn try_from_u8(val: u8) -> Result<Self, ()>;
@@ -70,10 +71,23 @@ pub trait DramSpec {
     fn update_state(&self, dram: &mut Dram<Self>, cmd: &Self::Command, child_id: u64);
     fn get_timming(&self, level: &Self::Level, cmd: &Self::Command) -> &[TimeEntry<Self::Command>];
     fn get_read_latency(&self) -> u64;
+    /// the clock period in ns
+    fn get_tck_ns(&self) -> f64;
+    /// the cycles from the write command to the end of the write data
+    fn get_write_latency(&self) -> u64;
     /// the number of reads per read req
     fn get_prefetch_size(&self) -> usize;
     /// the channel output bits
     fn get_channel_width(&self) -> usize;
+    /// the lower addr bits inside a request, they are ignored by the mapping
+    fn get_offset_bits(&self) -> usize;
+    /// the number of bursts(column commands) to transfer a request
+    fn get_request_bursts(&self) -> usize;
+    /// the number of command buses, a controller can issue one command per bus per cycle
+    /// - DDR4 has a single shared bus, HBM and LPDDR5 have separate row and column buses
+    fn get_cmd_bus_num(&self) -> usize;
+    /// the command bus that a command is issued on, should be less than `get_cmd_bus_num`
+    fn get_cmd_bus(&self, cmd: &Self::Command) -> usize;
 }
 
 #[derive(PartialEq, Eq)]
@@ -129,17 +143,16 @@ where
             id,
         }
     }
+    #[allow(clippy::only_used_in_recursion)]
     pub fn decode(&self, spec: &T, cmd: &T::Command, addr_vec: &[u64]) -> T::Command {
         let child_index = addr_vec[self.level.to_usize() + 1];
         if let Some(command) = T::get_pre_cmd(self, cmd, child_index) {
             tracing::debug!(level = ?self.level, ?command, "find pre command");
-            return command;
+            command
+        } else if self.level.is_bank() {
+            *cmd
         } else {
-            if self.level.is_bank() {
-                return *cmd;
-            } else {
-                return self.children[child_index as usize].decode(spec, cmd, addr_vec);
-            }
+            self.children[child_index as usize].decode(spec, cmd, addr_vec)
         }
     }
 
@@ -185,7 +198,7 @@ where
             if timing.sibling {
                 continue;
             }
-            let past = self.prev[cmd.to_usize()][timing.dist - 1 as usize];
+            let past = self.prev[cmd.to_usize()][timing.dist - 1];
             if past == u64::MAX {
                 continue;
             }
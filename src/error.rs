@@ -0,0 +1,74 @@
+//! the error type of ramu_rs
+
+/// the errors when building or using the memory
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read `{path}`: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to parse the config: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("the dram standard in the config is {found}, expected {expected}")]
+    WrongStandard {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("invalid dram organization: {0}")]
+    InvalidOrg(String),
+    #[error("invalid dram speed: {0}")]
+    InvalidSpeed(String),
+    #[error("invalid address mapping: {0}")]
+    InvalidMapping(String),
+    #[error("invalid address {addr:#x}, the memory capacity is {capacity:#x}")]
+    InvalidAddress { addr: u64, capacity: u64 },
+    #[error("invalid trace at line {line}: {message}")]
+    Trace { line: usize, message: String },
+    #[error("failed to read the trace at line {line}: {source}")]
+    TraceIo { line: usize, source: std::io::Error },
+    #[error("invalid binary trace at record {record}: {message}")]
+    BinaryTrace { record: u64, message: String },
+    #[error("failed to write the trace: {0}")]
+    TraceWrite(#[source] std::io::Error),
+    #[error("invalid generator: {0}")]
+    InvalidGenerator(String),
+    #[error("invalid core: {0}")]
+    InvalidCore(String),
+    #[error("invalid cache: {0}")]
+    InvalidCache(String),
+    #[error("a completion callback is set, the finished requests are not returned by try_recv")]
+    CallbackSet,
+    #[error("invalid config:{}", display_issues(.0))]
+    InvalidConfig(Vec<ConfigIssue>),
+}
+
+/// a problem found by `Config::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// the path of the field in the config, like `dram.org`
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`: {}", self.field, self.message)
+    }
+}
+
+fn display_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| format!("\n  {}", issue))
+        .collect()
+}
@@ -0,0 +1,364 @@
+//! synthetic traffic generators
+//! ## Example
+//! ```toml
+//! requests = 10000
+//! write_ratio = 0.3
+//! seed = 42
+//! [pattern]
+//! type = "HotSet"
+//! hot_size = 65536
+//! hot_ratio = 0.9
+//! ```
+
+use std::{fs, io::Write};
+
+use rand::{rngs::StdRng, RngExt, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    memory::AnyMemory,
+    request::{ReqType, Request},
+    trace::{BinaryTraceWriter, TraceRecord},
+};
+
+/// the address pattern of the generated requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Pattern {
+    /// `start`, `start + step`, `start + 2 * step`, ..., wrapped in the range
+    Sequential,
+    /// `start`, `start + stride`, `start + 2 * stride`, ..., wrapped in the range
+    Strided { stride: u64 },
+    /// uniformly random in the range
+    Random,
+    /// `hot_ratio` of the requests are random in the first `hot_size` bytes of the range,
+    /// the others are random in the whole range
+    HotSet { hot_size: u64, hot_ratio: f64 },
+    /// random rows of a few banks of channel 0, so most requests miss the open row
+    RowConflict {
+        #[serde(default = "default_banks")]
+        banks: usize,
+        #[serde(default = "default_rows")]
+        rows: usize,
+    },
+}
+
+fn default_banks() -> usize {
+    1
+}
+fn default_rows() -> usize {
+    64
+}
+fn default_step() -> u64 {
+    64
+}
+
+/// the config of a generator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorConfig {
+    pub pattern: Pattern,
+    /// the number of requests to generate
+    pub requests: u64,
+    /// the first addr of the range
+    #[serde(default)]
+    pub start: u64,
+    /// the bytes of the range, the whole memory after `start` by default
+    #[serde(default)]
+    pub range: Option<u64>,
+    /// the addr granularity of the sequential and random patterns
+    #[serde(default = "default_step")]
+    pub step: u64,
+    /// the probability of a request being a write
+    #[serde(default)]
+    pub write_ratio: f64,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl GeneratorConfig {
+    pub fn from_toml_path(path: &str) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_toml_str(&content)
+    }
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+/// generate the requests of a pattern, as an iterator
+pub struct Generator {
+    config: GeneratorConfig,
+    rng: StdRng,
+    /// the bytes of the range
+    range: u64,
+    /// the requests already generated
+    generated: u64,
+    /// the addrs of the row conflict pattern, indexed by bank and then row
+    conflict_addrs: Vec<Vec<u64>>,
+}
+
+impl Generator {
+    /// build a generator for the memory, the range and the rows are checked against it
+    pub fn new(config: GeneratorConfig, memory: &AnyMemory) -> Result<Self, Error> {
+        let invalid = |message: String| Error::InvalidGenerator(message);
+        let capacity = memory.capacity_bytes();
+        if config.start >= capacity {
+            return Err(invalid(format!(
+                "the start {:#x} is out of the capacity {:#x}",
+                config.start, capacity
+            )));
+        }
+        let range = config.range.unwrap_or(capacity - config.start);
+        if range == 0 || config.start + range > capacity {
+            return Err(invalid(format!(
+                "the range {:#x} from {:#x} is empty or out of the capacity {:#x}",
+                range, config.start, capacity
+            )));
+        }
+        if config.step == 0 || !(0.0..=1.0).contains(&config.write_ratio) {
+            return Err(invalid(format!(
+                "the step should not be 0 and the write_ratio should be in [0, 1], got {} and {}",
+                config.step, config.write_ratio
+            )));
+        }
+        let mut conflict_addrs = vec![];
+        match config.pattern {
+            Pattern::Strided { stride: 0 } => {
+                return Err(invalid("the stride should not be 0".to_string()))
+            }
+            Pattern::HotSet {
+                hot_size,
+                hot_ratio,
+            } if hot_size == 0 || hot_size > range || !(0.0..=1.0).contains(&hot_ratio) => {
+                return Err(invalid(format!(
+                    "the hot_size should be in (0, range] and the hot_ratio in [0, 1], got {} and {}",
+                    hot_size, hot_ratio
+                )));
+            }
+            Pattern::RowConflict { banks, rows } => {
+                conflict_addrs = Self::conflict_addrs(memory, banks, rows)?;
+            }
+            _ => {}
+        }
+        Ok(Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            range,
+            generated: 0,
+            conflict_addrs,
+        })
+    }
+
+    /// encode the addrs of `rows` rows in each of the first `banks` banks, the levels
+    /// between the rank and the row are the banks
+    fn conflict_addrs(
+        memory: &AnyMemory,
+        banks: usize,
+        rows: usize,
+    ) -> Result<Vec<Vec<u64>>, Error> {
+        let addr_size = memory.addr_size();
+        let row_level = addr_size.len() - 2;
+        let bank_levels = 2..row_level;
+        let total_banks: usize = addr_size[bank_levels.clone()].iter().product();
+        if banks == 0 || banks > total_banks || rows < 2 || rows > addr_size[row_level] {
+            return Err(Error::InvalidGenerator(format!(
+                "the row conflict pattern needs 1 to {} banks and 2 to {} rows, got {} and {}",
+                total_banks, addr_size[row_level], banks, rows
+            )));
+        }
+        Ok((0..banks)
+            .map(|bank| {
+                let mut addr_vec = vec![0; addr_size.len()];
+                let mut rest = bank;
+                for level in bank_levels.clone().rev() {
+                    addr_vec[level] = (rest % addr_size[level]) as u64;
+                    rest /= addr_size[level];
+                }
+                (0..rows)
+                    .map(|row| {
+                        addr_vec[row_level] = row as u64;
+                        memory.encode_addr(&addr_vec)
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn next_addr(&mut self) -> u64 {
+        let step = self.config.step;
+        let offset = match self.config.pattern {
+            Pattern::Sequential => (self.generated * step) % self.range,
+            Pattern::Strided { stride } => (self.generated * stride) % self.range,
+            Pattern::Random => self.random_in(self.range),
+            Pattern::HotSet {
+                hot_size,
+                hot_ratio,
+            } => {
+                if self.rng.random_bool(hot_ratio) {
+                    self.random_in(hot_size)
+                } else {
+                    self.random_in(self.range)
+                }
+            }
+            Pattern::RowConflict { .. } => {
+                let bank = self.rng.random_range(0..self.conflict_addrs.len());
+                let row = self.rng.random_range(0..self.conflict_addrs[bank].len());
+                return self.conflict_addrs[bank][row];
+            }
+        };
+        self.config.start + offset
+    }
+
+    /// a random offset aligned to the step in `[0, size)`
+    fn random_in(&mut self, size: u64) -> u64 {
+        let step = self.config.step;
+        self.rng.random_range(0..size.div_ceil(step)) * step
+    }
+
+    /// write the remaining requests to the binary trace
+    pub fn write_trace<W: Write>(self, writer: &mut BinaryTraceWriter<W>) -> Result<u64, Error> {
+        let mut count = 0;
+        for req in self {
+            writer.write(&TraceRecord::from_request(&req))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Request;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.generated >= self.config.requests {
+            return None;
+        }
+        let addr = self.next_addr();
+        let req_type = if self.rng.random_bool(self.config.write_ratio) {
+            ReqType::Write
+        } else {
+            ReqType::Read
+        };
+        self.generated += 1;
+        Some(Request::new(addr, req_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, replay::TimestampUnit};
+
+    fn new_generator(pattern: Pattern) -> Generator {
+        let memory = AnyMemory::new(Config::default()).unwrap();
+        let config = GeneratorConfig {
+            pattern,
+            requests: 1000,
+            start: 0x1000,
+            range: Some(1 << 20),
+            step: 64,
+            write_ratio: 0.25,
+            seed: 1,
+        };
+        Generator::new(config, &memory).unwrap()
+    }
+
+    #[test]
+    fn test_patterns() {
+        let addrs: Vec<_> = new_generator(Pattern::Sequential).map(|r| r.addr).collect();
+        assert_eq!(addrs.len(), 1000);
+        assert_eq!(&addrs[..3], [0x1000, 0x1040, 0x1080]);
+
+        let addrs: Vec<_> = new_generator(Pattern::Strided { stride: 1 << 19 })
+            .map(|r| r.addr)
+            .collect();
+        assert_eq!(&addrs[..3], [0x1000, 0x1000 + (1 << 19), 0x1000]);
+
+        let reqs: Vec<_> = new_generator(Pattern::Random).collect();
+        assert!(reqs
+            .iter()
+            .all(|r| (0x1000..0x1000 + (1 << 20)).contains(&r.addr) && r.addr % 64 == 0));
+        let writes = reqs.iter().filter(|r| r.req_type.is_write()).count();
+        assert!((150..350).contains(&writes), "{}", writes);
+        // the same seed generates the same requests
+        let again: Vec<_> = new_generator(Pattern::Random).map(|r| r.addr).collect();
+        assert_eq!(reqs.iter().map(|r| r.addr).collect::<Vec<_>>(), again);
+
+        let hot = new_generator(Pattern::HotSet {
+            hot_size: 4096,
+            hot_ratio: 0.9,
+        })
+        .filter(|r| r.addr < 0x1000 + 4096)
+        .count();
+        assert!(hot > 850, "{}", hot);
+    }
+
+    #[test]
+    fn test_row_conflict() {
+        let memory = AnyMemory::new(Config::default()).unwrap();
+        let generator = new_generator(Pattern::RowConflict { banks: 2, rows: 8 });
+        let mut rows = std::collections::BTreeSet::new();
+        for req in generator {
+            let addr_vec = memory.decode_addr(req.addr);
+            assert_eq!(&addr_vec[..3], [0, 0, 0]);
+            assert!(addr_vec[3] < 2 && addr_vec[4] < 8 && addr_vec[5] == 0);
+            rows.insert((addr_vec[3], addr_vec[4]));
+        }
+        assert_eq!(rows.len(), 16);
+
+        let mut memory = AnyMemory::new(Config::default()).unwrap();
+        let conflict = memory
+            .replay(
+                new_generator(Pattern::RowConflict {
+                    banks: 1,
+                    rows: 1 << 14,
+                })
+                .take(200)
+                .map(|r| Ok(TraceRecord::from_request(&r))),
+                TimestampUnit::Cycle,
+            )
+            .unwrap();
+        let mut memory = AnyMemory::new(Config::default()).unwrap();
+        let sequential = memory
+            .replay(
+                new_generator(Pattern::Sequential)
+                    .take(200)
+                    .map(|r| Ok(TraceRecord::from_request(&r))),
+                TimestampUnit::Cycle,
+            )
+            .unwrap();
+        assert!(conflict.cycles > 5 * sequential.cycles);
+    }
+
+    #[test]
+    fn test_invalid_generator() {
+        let memory = AnyMemory::new(Config::default()).unwrap();
+        let config = GeneratorConfig::from_toml_str(
+            r#"
+            requests = 10
+            write_ratio = 1.5
+            [pattern]
+            type = "Sequential"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Generator::new(config, &memory),
+            Err(Error::InvalidGenerator(_))
+        ));
+        let config = GeneratorConfig::from_toml_str(
+            r#"
+            requests = 10
+            [pattern]
+            type = "RowConflict"
+            rows = 1
+            "#,
+        )
+        .unwrap();
+        assert!(Generator::new(config, &memory).is_err());
+    }
+}
@@ -3,24 +3,29 @@
 //!
 //!
 
+pub mod cache;
 pub mod config;
 pub mod controller;
+pub mod cpu;
 pub mod ddr4;
 pub mod dram;
+pub mod error;
+pub mod generator;
 pub mod memory;
 pub(crate) mod refresh;
+pub mod replay;
 pub mod request;
 pub(crate) mod rowpolicy;
 pub(crate) mod rowtable;
 pub(crate) mod scheduler;
+pub mod trace;
 pub(crate) mod utils;
 
+use std::ffi::c_void;
+
 use config::Config;
-use ddr4::DDR4;
-use memory::{
-    MemoryTrait,
-    SimpleMemory::{self},
-};
+pub use error::Error;
+use memory::{AnyMemory, SendError};
 use request::{ReqType, Request};
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{
@@ -41,46 +46,158 @@ pub(crate) fn init_logger() {
         });
 }
 
-type SimpleDDR4 = SimpleMemory<DDR4>;
+type Memory = AnyMemory;
+type Generator = generator::Generator;
 
 #[cxx::bridge]
 mod ffi {
 
     extern "Rust" {
-        type SimpleDDR4;
+        type Memory;
         fn init_logger();
-        fn new_ddr4(config: &str) -> Box<SimpleDDR4>;
-        fn tick_ddr4(&mut self);
-        fn try_send_addr(&mut self, addr: u64, is_write: bool) -> bool;
-        fn try_recv_addr(&mut self, addr: &mut u64, is_write: &mut bool) -> bool;
+        fn new_memory(config: &str) -> Result<Box<Memory>>;
+        fn tick(&mut self);
+        fn try_send_addr(&mut self, addr: u64, is_write: bool, id: u64, tag: u64) -> Result<bool>;
+        fn try_recv_addr(
+            &mut self,
+            addr: &mut u64,
+            is_write: &mut bool,
+            id: &mut u64,
+            tag: &mut u64,
+        ) -> bool;
         fn get_cycle(&self) -> u64;
     }
+    extern "Rust" {
+        type Generator;
+        fn new_generator(config: &str, memory: &Memory) -> Result<Box<Generator>>;
+        fn try_next_addr(&mut self, addr: &mut u64, is_write: &mut bool) -> bool;
+    }
+}
+
+/// the completion callback of c, called with the context, addr, is_write, id and tag
+type CCallbackFn = extern "C" fn(*mut c_void, u64, bool, u64, u64);
+/// free the context of a c callback
+type CDropFn = extern "C" fn(*mut c_void);
+
+/// a c callback and its context, the context is freed when the callback is dropped
+struct CCallback {
+    callback: CCallbackFn,
+    context: *mut c_void,
+    drop_context: Option<CDropFn>,
+}
+impl CCallback {
+    fn call(&self, req: &Request) {
+        (self.callback)(
+            self.context,
+            req.addr,
+            req.req_type.is_write(),
+            req.id,
+            req.tag,
+        );
+    }
+}
+// SAFETY: the callback owns its context, which is only used by the thread ticking the memory.
+// the c side must make the callback and the context usable from that thread, like a
+// `std::function` without thread-local state
+unsafe impl Send for CCallback {}
+impl Drop for CCallback {
+    fn drop(&mut self) {
+        if let Some(drop_context) = self.drop_context {
+            drop_context(self.context);
+        }
+    }
 }
 
-pub fn new_ddr4<'a>(config: &str) -> Box<SimpleDDR4> {
-    let config = Config::from_toml_path(config);
-    let ddr4 = DDR4::new(&config);
-    Box::new(SimpleMemory::new(config, ddr4))
+/// call `callback` with each finished request in `tick` instead of returning it by
+/// `try_recv_addr`, `drop_context` is called when the callback is replaced or cleared,
+/// see `ramu_rs_callback.h` for the c++ wrapper taking a `std::function`.
+/// a null `callback` clears the callback like `ramu_rs_clear_callback`, and the context
+/// is dropped at once
+#[no_mangle]
+pub extern "C" fn ramu_rs_set_callback(
+    memory: &mut Memory,
+    callback: Option<CCallbackFn>,
+    context: *mut c_void,
+    drop_context: Option<CDropFn>,
+) {
+    let Some(callback) = callback else {
+        if let Some(drop_context) = drop_context {
+            drop_context(context);
+        }
+        memory.clear_callback();
+        return;
+    };
+    let callback = CCallback {
+        callback,
+        context,
+        drop_context,
+    };
+    memory.set_callback(move |req| callback.call(&req));
+}
+/// return the finished requests by `try_recv_addr` again
+#[no_mangle]
+pub extern "C" fn ramu_rs_clear_callback(memory: &mut Memory) {
+    memory.clear_callback();
 }
-impl SimpleDDR4 {
-    fn tick_ddr4(&mut self) {
-        self.tick();
+
+/// build a memory from the toml config file, the dram standard is selected by the config
+pub fn new_memory(config: &str) -> Result<Box<Memory>, Error> {
+    let config = Config::from_toml_path(config)?;
+    Ok(Box::new(AnyMemory::new(config)?))
+}
+/// build a traffic generator for the memory from the toml config file
+pub fn new_generator(config: &str, memory: &Memory) -> Result<Box<Generator>, Error> {
+    let config = generator::GeneratorConfig::from_toml_path(config)?;
+    Ok(Box::new(Generator::new(config, memory)?))
+}
+impl Generator {
+    /// return false if all the requests are generated
+    fn try_next_addr(&mut self, addr: &mut u64, is_write: &mut bool) -> bool {
+        if let Some(req) = self.next() {
+            *addr = req.addr;
+            *is_write = req.req_type.is_write();
+            true
+        } else {
+            false
+        }
     }
-    fn try_send_addr(&mut self, addr: u64, is_write: bool) -> bool {
-        self.try_send(Request::new(
-            addr,
-            if is_write {
-                ReqType::Write
-            } else {
-                ReqType::Read
-            },
-        ))
-        .is_ok()
+}
+impl Memory {
+    /// return false if the queue is full, and an error if the addr is rejected,
+    /// the id and the tag are returned with the request by `try_recv_addr`
+    fn try_send_addr(
+        &mut self,
+        addr: u64,
+        is_write: bool,
+        id: u64,
+        tag: u64,
+    ) -> Result<bool, Error> {
+        let req_type = if is_write {
+            ReqType::Write
+        } else {
+            ReqType::Read
+        };
+        match self.try_send(Request::new(addr, req_type).with_id(id).with_tag(tag)) {
+            Ok(()) => Ok(true),
+            Err(SendError::Full(_)) => Ok(false),
+            Err(SendError::OutOfRange(req)) => Err(Error::InvalidAddress {
+                addr: req.addr,
+                capacity: self.capacity_bytes(),
+            }),
+        }
     }
-    fn try_recv_addr(&mut self, addr: &mut u64, is_write: &mut bool) -> bool {
+    fn try_recv_addr(
+        &mut self,
+        addr: &mut u64,
+        is_write: &mut bool,
+        id: &mut u64,
+        tag: &mut u64,
+    ) -> bool {
         if let Some(req) = self.try_recv() {
             *addr = req.addr;
             *is_write = req.req_type.is_write();
+            *id = req.id;
+            *tag = req.tag;
             true
         } else {
             false
@@ -90,6 +207,8 @@ impl SimpleDDR4 {
 
 #[cfg(test)]
 mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use super::*;
     use crate::{
         config::Config,
@@ -102,7 +221,7 @@ mod test {
     fn test_memory_diffrent_row() {
         init_logger();
         let config = Config::default();
-        let ddr4 = DDR4::new(&config);
+        let ddr4 = DDR4::new(&config).unwrap();
 
         let mut mem = SimpleMemory::new(config, ddr4);
         let addr_vec_1 = [0, 0, 0, 0, 0, 0];
@@ -124,7 +243,7 @@ mod test {
     fn test_memory_write() {
         init_logger();
         let config = Config::default();
-        let ddr4 = DDR4::new(&config);
+        let ddr4 = DDR4::new(&config).unwrap();
 
         let mut mem = SimpleMemory::new(config, ddr4);
         let addr_vec_1 = [0, 0, 0, 0, 0, 0];
@@ -146,7 +265,7 @@ mod test {
     fn test_memory_read_and_write() {
         init_logger();
         let config = Config::default();
-        let ddr4 = DDR4::new(&config);
+        let ddr4 = DDR4::new(&config).unwrap();
 
         let mut mem = SimpleMemory::new(config, ddr4);
         let addr_vec_1 = [0, 0, 0, 0, 0, 0];
@@ -163,4 +282,58 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_memory_request_id() {
+        // the merged requests to the same addr keep their own ids and tags
+        let mut config = Config::default();
+        config.controller.merge_requests = true;
+        let mut mem = AnyMemory::new(config).unwrap();
+        assert!(mem.try_send_addr(0x40, false, 1, 10).unwrap());
+        assert!(mem.try_send_addr(0x40, false, 2, 20).unwrap());
+        let mut finished = vec![];
+        for _i in 0..100 {
+            mem.tick();
+            let (mut addr, mut is_write, mut id, mut tag) = (0, false, 0, 0);
+            while mem.try_recv_addr(&mut addr, &mut is_write, &mut id, &mut tag) {
+                assert_eq!((addr, is_write), (0x40, false));
+                finished.push((id, tag));
+            }
+        }
+        assert_eq!(finished, [(1, 10), (2, 20)]);
+    }
+
+    #[test]
+    fn test_c_callback() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn callback(_: *mut c_void, _: u64, _: bool, _: u64, _: u64) {
+            CALLED.fetch_add(1, Ordering::Relaxed);
+        }
+        extern "C" fn drop_context(_: *mut c_void) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut mem = AnyMemory::new(Config::default()).unwrap();
+        ramu_rs_set_callback(
+            &mut mem,
+            Some(callback),
+            std::ptr::null_mut(),
+            Some(drop_context),
+        );
+        assert!(mem.try_send_addr(0x40, false, 1, 10).unwrap());
+        for _i in 0..100 {
+            mem.tick();
+        }
+        assert_eq!(CALLED.load(Ordering::Relaxed), 1);
+        // a null callback clears the callback and drops both contexts
+        ramu_rs_set_callback(&mut mem, None, std::ptr::null_mut(), Some(drop_context));
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 2);
+        assert!(mem.try_send_addr(0x80, false, 2, 20).unwrap());
+        for _i in 0..100 {
+            mem.tick();
+        }
+        let (mut addr, mut is_write, mut id, mut tag) = (0, false, 0, 0);
+        assert!(mem.try_recv_addr(&mut addr, &mut is_write, &mut id, &mut tag));
+        assert_eq!(CALLED.load(Ordering::Relaxed), 1);
+    }
 }
@@ -3,32 +3,175 @@ use std::collections::VecDeque;
 use serde::{Deserialize, Serialize};
 
 use crate::dram::LevelTrait;
+use crate::utils;
 use crate::{
-    config::Config,
-    controller::Controller,
+    config::{Config, DramConfig},
+    controller::{Controller, ControllerStats},
+    ddr4::DDR4,
     dram::{Dram, DramSpec},
-    request::Request,
+    error::Error,
+    request::{ReqType, Request},
 };
 pub trait MemoryTrait {
     type T: DramSpec;
+    /// the clock period of the memory in ns
     fn clk_ns(&self) -> f64;
+    fn get_cycle(&self) -> u64;
     fn tick(&mut self);
-    fn try_send(&mut self, req: Request) -> Result<(), Request>;
+    /// send a request to the memory, return the request back in the error if it's not accepted
+    fn try_send(&mut self, req: Request) -> Result<(), SendError>;
     fn try_recv(&mut self) -> Option<Request>;
     fn pending_requests(&self) -> usize;
     fn finish(&mut self);
     fn get_spec(&self) -> &Self::T;
     fn decode_addr(&self, addr: u64) -> Vec<u64>;
-    /// return the addr represented by the addr_vec, note: the lower 6 bits are ignored
+    /// return the addr represented by the addr_vec, note: the offset bits are ignored
     fn encode_addr(&self, addr: &[u64]) -> u64;
+    /// the bytes that can be addressed in the memory
+    fn capacity_bytes(&self) -> u64;
+    /// the finished requests go to a completion callback instead of `try_recv`,
+    /// the drivers counting the finished requests by `try_recv` refuse such a memory
+    fn has_callback(&self) -> bool;
 }
 
+/// a borrowed memory is also a memory, so a [`Cache`](crate::cache::Cache) can be put in front of it
+impl<M: MemoryTrait> MemoryTrait for &mut M {
+    type T = M::T;
+    fn clk_ns(&self) -> f64 {
+        (**self).clk_ns()
+    }
+    fn get_cycle(&self) -> u64 {
+        (**self).get_cycle()
+    }
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+    fn try_send(&mut self, req: Request) -> Result<(), SendError> {
+        (**self).try_send(req)
+    }
+    fn try_recv(&mut self) -> Option<Request> {
+        (**self).try_recv()
+    }
+    fn pending_requests(&self) -> usize {
+        (**self).pending_requests()
+    }
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+    fn get_spec(&self) -> &Self::T {
+        (**self).get_spec()
+    }
+    fn decode_addr(&self, addr: u64) -> Vec<u64> {
+        (**self).decode_addr(addr)
+    }
+    fn encode_addr(&self, addr: &[u64]) -> u64 {
+        (**self).encode_addr(addr)
+    }
+    fn capacity_bytes(&self) -> u64 {
+        (**self).capacity_bytes()
+    }
+    fn has_callback(&self) -> bool {
+        (**self).has_callback()
+    }
+}
+
+/// the reason why a request is not accepted by `try_send`
+#[derive(Debug, enum_as_inner::EnumAsInner)]
+pub enum SendError {
+    /// the queue is full, the request can be sent again later
+    Full(Request),
+    /// the addr is out of the capacity and the policy is `OutOfRangePolicy::Reject`
+    OutOfRange(Request),
+}
+impl SendError {
+    pub fn into_request(self) -> Request {
+        match self {
+            SendError::Full(req) | SendError::OutOfRange(req) => req,
+        }
+    }
+}
+
+/// what to do when the addr of a request is out of the memory capacity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutOfRangePolicy {
+    /// reject the request with `SendError::OutOfRange`
+    Reject,
+    /// map the addr modulo the capacity
+    #[default]
+    Wrap,
+    /// panic with the addr and the capacity
+    Panic,
+}
+
+/// the statistics of the requests from one source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceStats {
+    pub reads: u64,
+    pub writes: u64,
+    /// the bytes of the finished requests
+    pub bytes: u64,
+    /// the sum of the cycles from accepted to finished
+    pub total_latency: u64,
+    pub max_latency: u64,
+    pub row_hits: u64,
+    pub row_misses: u64,
+}
+impl SourceStats {
+    fn record(&mut self, req: &Request, bytes: u64) {
+        match req.req_type {
+            ReqType::Read => self.reads += 1,
+            ReqType::Write => self.writes += 1,
+        }
+        self.bytes += bytes;
+        let latency = req.finish_time.saturating_sub(req.arrival_time);
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+        match req.row_hit {
+            Some(true) => self.row_hits += 1,
+            Some(false) => self.row_misses += 1,
+            None => {}
+        }
+    }
+    pub fn requests(&self) -> u64 {
+        self.reads + self.writes
+    }
+    /// the average latency in memory cycles
+    pub fn average_latency(&self) -> f64 {
+        if self.requests() == 0 {
+            return 0.;
+        }
+        self.total_latency as f64 / self.requests() as f64
+    }
+    /// the bandwidth in GB/s over `cycles` memory cycles of `clk_ns`
+    pub fn bandwidth_gbps(&self, cycles: u64, clk_ns: f64) -> f64 {
+        if cycles == 0 {
+            return 0.;
+        }
+        self.bytes as f64 / (cycles as f64 * clk_ns)
+    }
+    /// the fraction of the dram accesses that hit the open row
+    pub fn row_hit_rate(&self) -> f64 {
+        let accesses = self.row_hits + self.row_misses;
+        if accesses == 0 {
+            return 0.;
+        }
+        self.row_hits as f64 / accesses as f64
+    }
+}
+
+/// called with each finished request, see [`SimpleMemory::set_callback`]
+pub type Callback = Box<dyn FnMut(Request) + Send>;
+
 pub struct SimpleMemory<T: DramSpec> {
     config: Config,
     spec: T,
+    capacity: u64,
     clk: u64,
     controllers: Vec<Controller<T>>,
     ret_queue: VecDeque<Request>,
+    /// indexed by the source id
+    source_stats: Vec<SourceStats>,
+    callback: Option<Callback>,
 }
 impl<T> SimpleMemory<T>
 where
@@ -41,44 +184,156 @@ where
             let controller = Controller::new(&config, dram);
             controllers.push(controller);
         }
+        let capacity = spec
+            .get_full_addr_size()
+            .iter()
+            .map(|size| *size as u64)
+            .product::<u64>()
+            << spec.get_offset_bits();
+        tracing::info!(capacity, "memory capacity");
 
         SimpleMemory {
             config,
             spec,
+            capacity,
             clk: 0,
             controllers,
             ret_queue: Default::default(),
+            source_stats: vec![],
+            callback: None,
+        }
+    }
+    /// the statistics of all the controllers
+    pub fn stats(&self) -> ControllerStats {
+        let mut stats = ControllerStats::default();
+        for controller in self.controllers.iter() {
+            stats.merge(&controller.stats);
+        }
+        stats
+    }
+    /// the statistics of each source, indexed by the source id
+    pub fn source_stats(&self) -> &[SourceStats] {
+        &self.source_stats
+    }
+    /// call the callback in `tick` with each finished request instead of returning it by
+    /// `try_recv`, the drivers like [`Cache::new`](crate::cache::Cache::new) return [`Error::CallbackSet`] while it's set
+    pub fn set_callback(&mut self, callback: impl FnMut(Request) + Send + 'static) {
+        self.callback = Some(Box::new(callback));
+    }
+    /// return the finished requests by `try_recv` again
+    pub fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+}
+/// a memory of any supported dram standard, the standard is selected by the config
+pub enum AnyMemory {
+    DDR4(SimpleMemory<DDR4>),
+}
+/// dispatch a method call to the memory of the actual dram standard
+macro_rules! dispatch {
+    ($self:expr, $mem:ident => $body:expr) => {
+        match $self {
+            $crate::memory::AnyMemory::DDR4($mem) => $body,
+        }
+    };
+}
+pub(crate) use dispatch;
+impl AnyMemory {
+    /// build the dram spec and the memory according to the standard in the config
+    pub fn new(config: Config) -> Result<Self, Error> {
+        config.validate()?;
+        match config.dram {
+            DramConfig::DDR4(_) => {
+                let ddr4 = DDR4::new(&config)?;
+                Ok(AnyMemory::DDR4(SimpleMemory::new(config, ddr4)))
+            }
         }
     }
     pub fn get_cycle(&self) -> u64 {
-        self.clk
+        dispatch!(self, mem => mem.get_cycle())
+    }
+    pub fn clk_ns(&self) -> f64 {
+        dispatch!(self, mem => mem.clk_ns())
+    }
+    pub fn tick(&mut self) {
+        dispatch!(self, mem => mem.tick())
+    }
+    pub fn try_send(&mut self, req: Request) -> Result<(), SendError> {
+        dispatch!(self, mem => mem.try_send(req))
+    }
+    pub fn try_recv(&mut self) -> Option<Request> {
+        dispatch!(self, mem => mem.try_recv())
+    }
+    pub fn pending_requests(&self) -> usize {
+        dispatch!(self, mem => mem.pending_requests())
+    }
+    pub fn decode_addr(&self, addr: u64) -> Vec<u64> {
+        dispatch!(self, mem => mem.decode_addr(addr))
+    }
+    pub fn encode_addr(&self, addr: &[u64]) -> u64 {
+        dispatch!(self, mem => mem.encode_addr(addr))
+    }
+    pub fn capacity_bytes(&self) -> u64 {
+        dispatch!(self, mem => mem.capacity_bytes())
+    }
+    /// the size of each level, from the channel to the column
+    pub fn addr_size(&self) -> Vec<usize> {
+        dispatch!(self, mem => mem.get_spec().get_full_addr_size().to_vec())
+    }
+    pub fn stats(&self) -> ControllerStats {
+        dispatch!(self, mem => mem.stats())
+    }
+    pub fn source_stats(&self) -> &[SourceStats] {
+        dispatch!(self, mem => mem.source_stats())
+    }
+    /// see [`SimpleMemory::set_callback`]
+    pub fn set_callback(&mut self, callback: impl FnMut(Request) + Send + 'static) {
+        dispatch!(self, mem => mem.set_callback(callback))
+    }
+    pub fn clear_callback(&mut self) {
+        dispatch!(self, mem => mem.clear_callback())
     }
 }
-#[derive(Debug, Serialize, Deserialize)]
+
+#[derive(Debug, Clone, Serialize, Deserialize, enum_as_inner::EnumAsInner)]
 
 pub enum MappingType {
     ChRaBaRoCo,
     RoBaRaCoCh,
     CoRoBaRaCh,
     RoCoBaRaCh,
+    /// a bit-level mapping, see [`BitMapping`]
+    Bits(BitMapping),
+    /// a base mapping with XOR hashing on top of it, see [`XorMapping`]
+    Xor(XorMapping),
 }
 impl MappingType {
+    /// the bits of the user segments of a bit-level mapping, `None` for the other mappings
+    pub fn segment_bits(&self) -> Option<usize> {
+        match self {
+            MappingType::Bits(bit_mapping) => Some(bit_mapping.total_bits()),
+            MappingType::Xor(xor_mapping) => xor_mapping.base.segment_bits(),
+            _ => None,
+        }
+    }
     /// get the slicing sequence of the address,
     /// - `0` represent the channel, the `last` represent the column,
     /// - the `frist entry` represent the ***least*** significant bit in addr
     /// - the `last entry` represent the ***most significant*** bit in addr
+    /// - return `None` for the bit-level mapping, which is not sliced by whole levels
     /// ## Example
     /// `[0,1,2,3,4]` means `__co__ro__ba__ra__ch__`, which is, first slice `ch`, then `ra`, `ba`, `ro`, `co`
     ///
     ///## Arguments
     /// - bg: contains bank group in level
-    pub fn get_slice_sequence(&self, bg: bool) -> &'static [usize] {
-        if !bg {
+    pub fn get_slice_sequence(&self, bg: bool) -> Option<&'static [usize]> {
+        let sequence: &'static [usize] = if !bg {
             match self {
                 MappingType::ChRaBaRoCo => &[4, 3, 2, 1, 0],
                 MappingType::RoBaRaCoCh => &[0, 4, 1, 2, 3],
                 MappingType::CoRoBaRaCh => &[0, 1, 2, 3, 4],
                 MappingType::RoCoBaRaCh => &[0, 1, 2, 4, 3],
+                MappingType::Bits(_) | MappingType::Xor(_) => return None,
             }
         } else {
             match self {
@@ -86,8 +341,259 @@ impl MappingType {
                 MappingType::RoBaRaCoCh => &[0, 5, 1, 3, 2, 4],
                 MappingType::CoRoBaRaCh => &[0, 1, 3, 2, 4, 5],
                 MappingType::RoCoBaRaCh => &[0, 1, 3, 2, 5, 4],
+                MappingType::Bits(_) | MappingType::Xor(_) => return None,
+            }
+        };
+        Some(sequence)
+    }
+    /// map the addr(without the offset bits) into addr_vec
+    pub fn setup_addr_vec(&self, addr: u64, addr_bits: &[usize], addr_vec: &mut [u64], bg: bool) {
+        match self {
+            MappingType::Bits(bit_mapping) => {
+                utils::setup_addr_vec_by_segments(addr, addr_vec, &bit_mapping.get_segments(bg))
+            }
+            MappingType::Xor(xor_mapping) => {
+                xor_mapping
+                    .base
+                    .setup_addr_vec(addr, addr_bits, addr_vec, bg);
+                xor_mapping.hash(addr, addr_vec, bg);
+            }
+            _ => utils::setup_addr_vec(
+                addr,
+                addr_bits,
+                addr_vec,
+                self.get_slice_sequence(bg).unwrap(),
+            ),
+        }
+    }
+    /// return the addr(without the offset bits) represented by the addr_vec
+    pub fn set_up_addr(&self, addr_vec: &[u64], addr_bits: &[usize], bg: bool) -> u64 {
+        match self {
+            MappingType::Bits(bit_mapping) => {
+                utils::set_up_addr_by_segments(addr_vec, &bit_mapping.get_segments(bg))
+            }
+            MappingType::Xor(xor_mapping) => {
+                // the hash sources are not hashed, so they are the same in the hashed addr
+                let hashed_addr = xor_mapping.base.set_up_addr(addr_vec, addr_bits, bg);
+                let mut addr_vec = addr_vec.to_vec();
+                xor_mapping.hash(hashed_addr, &mut addr_vec, bg);
+                xor_mapping.base.set_up_addr(&addr_vec, addr_bits, bg)
+            }
+            _ => utils::set_up_addr(addr_vec, addr_bits, self.get_slice_sequence(bg).unwrap()),
+        }
+    }
+    /// check if the mapping covers exactly the bits of each level
+    pub fn check(&self, addr_bits: &[usize], bg: bool) -> Result<(), String> {
+        match self {
+            MappingType::Bits(bit_mapping) => bit_mapping.check(addr_bits, bg),
+            MappingType::Xor(xor_mapping) => xor_mapping.check(addr_bits, bg),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// the level names used in the bit-level and XOR mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MappingLevel {
+    Ch,
+    Ra,
+    Bg,
+    Ba,
+    Ro,
+    Co,
+}
+impl MappingLevel {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Ch" => Some(MappingLevel::Ch),
+            "Ra" => Some(MappingLevel::Ra),
+            "Bg" => Some(MappingLevel::Bg),
+            "Ba" => Some(MappingLevel::Ba),
+            "Ro" => Some(MappingLevel::Ro),
+            "Co" => Some(MappingLevel::Co),
+            _ => None,
+        }
+    }
+    /// the index of the level in addr_vec, `None` for bank group if there is no bank group
+    fn to_index(self, bg: bool) -> Option<usize> {
+        let offset = bg as usize;
+        match self {
+            MappingLevel::Ch => Some(0),
+            MappingLevel::Ra => Some(1),
+            MappingLevel::Bg => bg.then_some(2),
+            MappingLevel::Ba => Some(2 + offset),
+            MappingLevel::Ro => Some(3 + offset),
+            MappingLevel::Co => Some(4 + offset),
+        }
+    }
+}
+
+/// a bit-level address mapping, written as a string of segments like `"Ro:15 Bg:1 Ba:2 Co:4 Bg:1 Co:3"`
+/// - the segments are listed from the most significant bit to the least significant bit of the addr
+/// - each segment is `Level:bits`, or `Level` for a single bit, so the mapping can also be written bit by bit
+/// - when a level is split into several segments, the lower segment holds the lower bits of that level
+/// - the levels are `Ch`, `Ra`, `Bg`, `Ba`, `Ro` and `Co`
+/// ## Example
+/// ```toml
+/// mapping_type = { Bits = "Ro:15 Bg:1 Ba:2 Co:4 Bg:1 Co:3" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BitMapping {
+    /// the segments from the most significant bit to the least significant bit
+    segments: Vec<(MappingLevel, usize)>,
+    text: String,
+}
+impl BitMapping {
+    pub fn new(text: &str) -> Result<Self, String> {
+        let segments = text
+            .split_whitespace()
+            .map(|segment| {
+                let (name, bits) = match segment.split_once(':') {
+                    Some((name, bits)) => (
+                        name,
+                        bits.parse::<usize>().map_err(|e| {
+                            format!("invalid bits `{}` in segment `{}`: {}", bits, segment, e)
+                        })?,
+                    ),
+                    None => (segment, 1),
+                };
+                let level = MappingLevel::from_name(name).ok_or_else(|| {
+                    format!(
+                        "unknown level `{}` in segment `{}`, valid levels are: Ch, Ra, Bg, Ba, Ro, Co",
+                        name, segment
+                    )
+                })?;
+                Ok((level, bits))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            segments,
+            text: text.to_string(),
+        })
+    }
+    /// the segments as `(level index, bits)` from the least significant bit to the most significant bit
+    pub fn get_segments(&self, bg: bool) -> Vec<(usize, usize)> {
+        self.segments
+            .iter()
+            .rev()
+            .map(|(level, bits)| {
+                (
+                    level.to_index(bg).expect("no bank group in this dram"),
+                    *bits,
+                )
+            })
+            .collect()
+    }
+    /// the bits of all the segments
+    pub fn total_bits(&self) -> usize {
+        self.segments.iter().map(|(_, bits)| bits).sum()
+    }
+    /// check if the mapping covers exactly the bits of each level
+    pub fn check(&self, addr_bits: &[usize], bg: bool) -> Result<(), String> {
+        let mut total_bits = vec![0; addr_bits.len()];
+        for (level, bits) in self.segments.iter() {
+            let index = level
+                .to_index(bg)
+                .ok_or_else(|| format!("level `{:?}` is not in this dram", level))?;
+            total_bits[index] += bits;
+        }
+        for (index, (mapped, expected)) in total_bits.iter().zip(addr_bits).enumerate() {
+            if mapped != expected {
+                return Err(format!(
+                    "the mapping `{}` has {} bits for level {}, but the dram needs {} bits",
+                    self.text, mapped, index, expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+/// XOR hashing on top of a base mapping, to spread the row conflicts over banks and channels
+/// - the masks select bits of the addr without the offset bits, the same bits the base mapping slices
+/// - a mask should not select any bit of a hashed level, so the hashing can be inverted
+/// ## Example
+/// XOR the two bank bits with the two lowest row bits of `RoBaRaCoCh` in the default DDR4
+/// ```toml
+/// [mapping_type.Xor]
+/// base = "RoBaRaCoCh"
+/// hashes = [{ level = "Ba", masks = [0x800, 0x1000] }]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XorMapping {
+    pub base: Box<MappingType>,
+    pub hashes: Vec<XorHash>,
+}
+/// the XOR hash of one level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XorHash {
+    pub level: MappingLevel,
+    /// one mask per bit of the level from the lowest bit, the parity of `addr & mask` is XORed into that bit
+    pub masks: Vec<u64>,
+}
+impl XorMapping {
+    /// XOR the hash of addr into addr_vec, applying it twice restores the addr_vec
+    fn hash(&self, addr: u64, addr_vec: &mut [u64], bg: bool) {
+        for hash in self.hashes.iter() {
+            let index = hash.level.to_index(bg).expect("no bank group in this dram");
+            for (bit, mask) in hash.masks.iter().enumerate() {
+                addr_vec[index] ^= ((addr & mask).count_ones() as u64 & 1) << bit;
+            }
+        }
+    }
+    /// check if the hashing is valid and can be inverted
+    pub fn check(&self, addr_bits: &[usize], bg: bool) -> Result<(), String> {
+        self.base.check(addr_bits, bg)?;
+        // the addr bits that belong to the hashed levels
+        let mut hashed_levels = vec![0; addr_bits.len()];
+        for hash in self.hashes.iter() {
+            let index = hash
+                .level
+                .to_index(bg)
+                .ok_or_else(|| format!("level `{:?}` is not in this dram", hash.level))?;
+            if hash.masks.len() > addr_bits[index] {
+                return Err(format!(
+                    "{} masks for level `{:?}`, but it only has {} bits",
+                    hash.masks.len(),
+                    hash.level,
+                    addr_bits[index]
+                ));
             }
+            hashed_levels[index] = (1 << addr_bits[index]) - 1;
         }
+        let hashed_bits = self.base.set_up_addr(&hashed_levels, addr_bits, bg);
+        let total_bits: usize = addr_bits.iter().sum();
+        for hash in self.hashes.iter() {
+            for mask in hash.masks.iter() {
+                if mask & hashed_bits != 0 {
+                    return Err(format!(
+                        "the mask {:#x} of level `{:?}` selects the bits {:#x} of a hashed level",
+                        mask,
+                        hash.level,
+                        mask & hashed_bits
+                    ));
+                }
+                if mask >> total_bits != 0 {
+                    return Err(format!(
+                        "the mask {:#x} of level `{:?}` selects bits above the {} mapped bits",
+                        mask, hash.level, total_bits
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for BitMapping {
+    type Error = String;
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::new(&text)
+    }
+}
+impl From<BitMapping> for String {
+    fn from(bit_mapping: BitMapping) -> Self {
+        bit_mapping.text
     }
 }
 
@@ -97,27 +603,61 @@ where
 {
     type T = T;
     fn clk_ns(&self) -> f64 {
-        todo!()
+        self.spec.get_tck_ns()
+    }
+
+    fn get_cycle(&self) -> u64 {
+        self.clk
     }
 
     fn tick(&mut self) {
         self.clk += 1;
         for controller in self.controllers.iter_mut() {
             controller.tick(&self.spec, self.clk);
-            if let Some(req) = controller.finished_queue.pop_front() {
-                self.ret_queue.push_back(req);
+            // the merged requests finish together
+            for req in controller.finished_queue.drain(..) {
+                if self.source_stats.len() <= req.source_id {
+                    self.source_stats
+                        .resize_with(req.source_id + 1, Default::default);
+                }
+                self.source_stats[req.source_id].record(&req, self.config.request_size as u64);
+                match &mut self.callback {
+                    Some(callback) => callback(req),
+                    None => self.ret_queue.push_back(req),
+                }
             }
         }
     }
 
-    fn try_send(&mut self, mut req: Request) -> Result<(), Request> {
-        // init the addr vec!
-        if !req.done_setup {
-            let decoded_addr = self.spec.decode_addr(req.addr, &self.config.mapping_type);
+    fn try_send(&mut self, mut req: Request) -> Result<(), SendError> {
+        // init the addr vec! a decoded addr_vec that doesn't fit this memory is decoded again
+        let addr_size = self.spec.get_full_addr_size();
+        let fits = req.addr_vec.len() == addr_size.len()
+            && req
+                .addr_vec
+                .iter()
+                .zip(addr_size)
+                .all(|(index, size)| *index < *size as u64);
+        if !req.done_setup || !fits {
+            let mut addr = req.addr;
+            if addr >= self.capacity {
+                match self.config.out_of_range {
+                    OutOfRangePolicy::Reject => return Err(SendError::OutOfRange(req)),
+                    OutOfRangePolicy::Wrap => addr %= self.capacity,
+                    OutOfRangePolicy::Panic => panic!(
+                        "the addr {:#x} is out of the memory capacity {:#x}",
+                        addr, self.capacity
+                    ),
+                }
+            }
+            let decoded_addr = self.spec.decode_addr(addr, &self.config.mapping_type);
             req.addr_vec = decoded_addr;
             req.done_setup = true;
         }
-        self.controllers[req.addr_vec[0] as usize].try_enqueue(req)
+        req.arrival_time = self.clk;
+        self.controllers[req.addr_vec[0] as usize]
+            .try_enqueue(req)
+            .map_err(SendError::Full)
     }
 
     fn pending_requests(&self) -> usize {
@@ -151,4 +691,117 @@ where
     fn encode_addr(&self, addr: &[u64]) -> u64 {
         self.spec.encode_addr(addr, &self.config.mapping_type)
     }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.capacity
+    }
+
+    fn has_callback(&self) -> bool {
+        self.callback.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{init_logger, request::ReqType};
+
+    #[test]
+    fn test_out_of_range() {
+        init_logger();
+        let config = Config {
+            out_of_range: OutOfRangePolicy::Reject,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut mem = SimpleMemory::new(config, ddr4);
+        // 4Gb x8 has 8 chips in a rank
+        let capacity = mem.capacity_bytes();
+        assert_eq!(capacity, 4 << 30);
+        mem.try_send(Request::new(capacity - 64, ReqType::Read))
+            .unwrap();
+        let err = mem
+            .try_send(Request::new(capacity, ReqType::Read))
+            .unwrap_err();
+        assert_eq!(err.as_out_of_range().unwrap().addr, capacity);
+
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut mem = SimpleMemory::new(config, ddr4);
+        mem.try_send(Request::new(capacity + 64, ReqType::Read))
+            .unwrap();
+        let req = &mem.controllers[0].read_queue.queue[0];
+        assert_eq!(req.addr, capacity + 64);
+        assert_eq!(req.addr_vec, mem.decode_addr(64));
+
+        // a decoded addr_vec from a larger memory goes through the policy again
+        let config = Config {
+            out_of_range: OutOfRangePolicy::Reject,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut mem = SimpleMemory::new(config, ddr4);
+        let mut req = Request::new(capacity * 2, ReqType::Read);
+        req.addr_vec = vec![1, 0, 0, 0, 0, 0];
+        req.done_setup = true;
+        let err = mem.try_send(req).unwrap_err();
+        assert_eq!(err.as_out_of_range().unwrap().addr, capacity * 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of the memory capacity")]
+    fn test_out_of_range_panic() {
+        let config = Config {
+            out_of_range: OutOfRangePolicy::Panic,
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut mem = SimpleMemory::new(config, ddr4);
+        let capacity = mem.capacity_bytes();
+        let _ = mem.try_send(Request::new(capacity, ReqType::Read));
+    }
+
+    #[test]
+    fn test_callback() {
+        use std::sync::{Arc, Mutex};
+
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut mem = SimpleMemory::new(config, ddr4);
+        let finished = Arc::new(Mutex::new(vec![]));
+        let callback_finished = finished.clone();
+        mem.set_callback(move |req| {
+            callback_finished
+                .lock()
+                .unwrap()
+                .push((req.id, req.finish_time))
+        });
+        mem.try_send(Request::new_read(0).with_id(1)).unwrap();
+        mem.try_send(Request::new_write(0x10000).with_id(2))
+            .unwrap();
+        // the memory with a callback can still be ticked by another thread
+        let mut mem = std::thread::spawn(move || {
+            for _ in 0..100 {
+                mem.tick();
+                assert!(mem.try_recv().is_none());
+            }
+            mem
+        })
+        .join()
+        .unwrap();
+        let mut finished = finished.lock().unwrap().clone();
+        finished.sort();
+        assert_eq!(
+            finished.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert!(finished.iter().all(|(_, finish_time)| *finish_time > 0));
+
+        mem.clear_callback();
+        mem.try_send(Request::new_read(0x40).with_id(3)).unwrap();
+        for _ in 0..100 {
+            mem.tick();
+        }
+        assert_eq!(mem.try_recv().unwrap().id, 3);
+    }
 }
@@ -0,0 +1,193 @@
+//! replay a trace with timestamps, each request is sent no earlier than its timestamp
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    memory::{dispatch, AnyMemory, MemoryTrait, SendError},
+    trace::TraceRecord,
+};
+
+/// the unit of the timestamps in the trace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimestampUnit {
+    /// memory cycles
+    #[default]
+    Cycle,
+    /// ns, converted to memory cycles by the clock period
+    Ns,
+}
+impl TimestampUnit {
+    /// the first memory cycle not earlier than the timestamp
+    pub fn to_cycles(&self, timestamp: u64, clk_ns: f64) -> u64 {
+        match self {
+            TimestampUnit::Cycle => timestamp,
+            TimestampUnit::Ns => (timestamp as f64 / clk_ns).ceil() as u64,
+        }
+    }
+}
+
+/// the result of a replay
+#[derive(Debug, Clone, Default)]
+pub struct ReplayStats {
+    /// the cycle when all the requests are finished
+    pub cycles: u64,
+    /// the cycles each request waits for a free queue entry after its timestamp,
+    /// in the order of the trace
+    pub queueing_delays: Vec<u64>,
+}
+impl ReplayStats {
+    pub fn average_queueing_delay(&self) -> f64 {
+        if self.queueing_delays.is_empty() {
+            return 0.;
+        }
+        self.queueing_delays.iter().sum::<u64>() as f64 / self.queueing_delays.len() as f64
+    }
+    pub fn max_queueing_delay(&self) -> u64 {
+        self.queueing_delays.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// tick the memory and receive the finished requests
+fn tick<M: MemoryTrait>(memory: &mut M, on_going: &mut u64) {
+    memory.tick();
+    while memory.try_recv().is_some() {
+        *on_going -= 1;
+    }
+}
+
+/// send the records in order, each one no earlier than its timestamp, and wait until all
+/// of them are finished. a record without a timestamp is sent right after the previous one.
+/// the memory should not have a completion callback, see [`MemoryTrait::has_callback`]
+pub fn replay<M: MemoryTrait>(
+    memory: &mut M,
+    records: impl IntoIterator<Item = Result<TraceRecord, Error>>,
+    unit: TimestampUnit,
+) -> Result<ReplayStats, Error> {
+    if memory.has_callback() {
+        return Err(Error::CallbackSet);
+    }
+    let clk_ns = memory.clk_ns();
+    let mut stats = ReplayStats::default();
+    let mut on_going = 0;
+    for record in records {
+        let record = record?;
+        let inject_cycle = match record.timestamp {
+            Some(timestamp) => unit.to_cycles(timestamp, clk_ns),
+            None => memory.get_cycle(),
+        };
+        while memory.get_cycle() < inject_cycle {
+            tick(memory, &mut on_going);
+        }
+        let mut req = record.into_request();
+        loop {
+            match memory.try_send(req) {
+                Ok(()) => break,
+                Err(SendError::Full(full)) => {
+                    req = full;
+                    tick(memory, &mut on_going);
+                }
+                Err(SendError::OutOfRange(req)) => {
+                    return Err(Error::InvalidAddress {
+                        addr: req.addr,
+                        capacity: memory.capacity_bytes(),
+                    })
+                }
+            }
+        }
+        on_going += 1;
+        stats
+            .queueing_delays
+            .push(memory.get_cycle().saturating_sub(inject_cycle));
+    }
+    while on_going != 0 {
+        tick(memory, &mut on_going);
+    }
+    stats.cycles = memory.get_cycle();
+    Ok(stats)
+}
+
+impl AnyMemory {
+    /// replay a trace with timestamps, see [`replay`]
+    pub fn replay(
+        &mut self,
+        records: impl IntoIterator<Item = Result<TraceRecord, Error>>,
+        unit: TimestampUnit,
+    ) -> Result<ReplayStats, Error> {
+        dispatch!(self, mem => replay(mem, records, unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config, controller::ControllerConfig, ddr4::DDR4, memory::SimpleMemory,
+        request::ReqType, trace::TimedTraceReader,
+    };
+
+    #[test]
+    fn test_replay() {
+        let config = Config {
+            controller: ControllerConfig {
+                read_queue: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut memory = SimpleMemory::new(config, ddr4);
+        // sent at 100 and scheduled from 101, finishes after 36(nRCD + nCL + nBL)
+        let stats = replay(
+            &mut memory,
+            TimedTraceReader::new("100 0x0 R\n".as_bytes()),
+            TimestampUnit::Cycle,
+        )
+        .unwrap();
+        assert_eq!(stats.queueing_delays, [0]);
+        assert_eq!(stats.cycles, 137);
+
+        // the second read waits for the first one to leave the read queue
+        let records = (0..2).map(|i| {
+            Ok(TraceRecord {
+                addr: i << 20,
+                req_type: ReqType::Read,
+                timestamp: Some(200),
+            })
+        });
+        let stats = replay(&mut memory, records, TimestampUnit::Cycle).unwrap();
+        assert_eq!(stats.queueing_delays.len(), 2);
+        assert_eq!(stats.queueing_delays[0], 0);
+        assert!(stats.queueing_delays[1] > 0);
+        assert_eq!(stats.max_queueing_delay(), stats.queueing_delays[1]);
+    }
+
+    #[test]
+    fn test_replay_callback() {
+        // the finished requests never reach try_recv, so the replay refuses to start
+        let config = Config::default();
+        let ddr4 = DDR4::new(&config).unwrap();
+        let mut memory = SimpleMemory::new(config, ddr4);
+        memory.set_callback(|_| {});
+        let result = replay(
+            &mut memory,
+            TimedTraceReader::new("0 0x0 R\n".as_bytes()),
+            TimestampUnit::Cycle,
+        );
+        assert!(matches!(result, Err(Error::CallbackSet)));
+        memory.clear_callback();
+        let result = replay(
+            &mut memory,
+            TimedTraceReader::new("0 0x0 R\n".as_bytes()),
+            TimestampUnit::Cycle,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_timestamp_unit() {
+        // DDR4-2400 runs at 0.833ns
+        assert_eq!(TimestampUnit::Ns.to_cycles(10, 0.833), 13);
+        assert_eq!(TimestampUnit::Cycle.to_cycles(10, 0.833), 10);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Copy, enum_as_inner::EnumAsInner)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, enum_as_inner::EnumAsInner)]
 pub enum ReqType {
     Read,
     Write,
@@ -7,10 +7,23 @@ pub enum ReqType {
 pub struct Request {
     pub addr: u64,
     pub addr_vec: Vec<u64>,
-    pub done_setup: bool,
+    /// the addr_vec is decoded by the memory, only the memory sets it
+    pub(crate) done_setup: bool,
     pub req_type: ReqType,
     pub arrival_time: u64,
     pub finish_time: u64,
+    /// the bursts already issued, a request may need several bursts
+    pub issued_bursts: usize,
+    /// the duplicated requests merged into this one, they finish together with it
+    pub merged: Vec<Request>,
+    /// the core or the frontend that sent the request
+    pub source_id: usize,
+    /// the id given by the caller, returned unchanged
+    pub id: u64,
+    /// an opaque value of the caller, returned unchanged
+    pub tag: u64,
+    /// whether the request found its row open, `None` if it's served without accessing the dram
+    pub row_hit: Option<bool>,
 }
 impl Request {
     pub fn new(addr: u64, req_type: ReqType) -> Self {
@@ -21,6 +34,12 @@ impl Request {
             req_type,
             arrival_time: 0,
             finish_time: 0,
+            issued_bursts: 0,
+            merged: Vec::new(),
+            source_id: 0,
+            id: 0,
+            tag: 0,
+            row_hit: None,
         }
     }
     pub fn new_read(addr: u64) -> Self {
@@ -29,4 +48,16 @@ impl Request {
     pub fn new_write(addr: u64) -> Self {
         Self::new(addr, ReqType::Write)
     }
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+    pub fn with_tag(mut self, tag: u64) -> Self {
+        self.tag = tag;
+        self
+    }
+    pub fn with_source(mut self, source_id: usize) -> Self {
+        self.source_id = source_id;
+        self
+    }
 }
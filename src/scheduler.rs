@@ -4,6 +4,7 @@ use crate::{
     request::Request,
 };
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Default)]
 pub enum SchedulerType {
     #[default]
@@ -22,12 +23,10 @@ impl Scheduler {
         _dram: &Dram<T>,
     ) -> Option<(usize, &'b Request)> {
         if queue.size() == 0 {
-            return None;
+            None
         } else {
             match self.scheduler_type {
-                SchedulerType::FCFS => {
-                    return Some((0, &queue.queue[0]));
-                }
+                SchedulerType::FCFS => Some((0, &queue.queue[0])),
                 SchedulerType::FRFCFS => None,
             }
         }
@@ -35,7 +34,6 @@ impl Scheduler {
 }
 
 #[cfg(test)]
-
 mod tests {
 
     struct A {
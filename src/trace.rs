@@ -0,0 +1,829 @@
+//! the readers of the ramulator trace formats
+//! - memory trace: `<addr> <R|W>` per line, the addr is in hex, like `0x12345680 R`
+//! - cpu trace: `<bubble count> <read addr> [<writeback addr>]` per line, the addrs are
+//!   decimal or hex with `0x`
+//! - timed trace: the memory trace with a leading timestamp, like `100 0x12345680 R`
+//! - DRAMSim3 trace: `<addr> <READ|WRITE> <cycle>` per line, like `0x12345680 READ 100`
+//! - ChampSim trace: the binary instruction records, only the memory operands are used
+//!
+//! empty lines are skipped, a bad line is reported with its line number
+//!
+//! the traces can be compressed by gzip, zstd or xz, they are detected by the magic bytes.
+//! a trace can also be converted to the binary format, see [`BinaryTraceWriter`]
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+};
+
+use crate::{
+    error::Error,
+    request::{ReqType, Request},
+};
+
+/// one line of the cpu trace
+#[derive(Debug)]
+pub struct CpuTraceEntry {
+    /// the non-memory instructions before the read
+    pub bubbles: u64,
+    pub read: Request,
+    /// the dirty line evicted by the read
+    pub writeback: Option<Request>,
+}
+
+/// read the lines of a trace, skip the empty ones and count the line number
+struct TraceLines<R> {
+    reader: R,
+    line_number: usize,
+    buf: String,
+}
+impl<R: BufRead> TraceLines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line_number: 0,
+            buf: String::new(),
+        }
+    }
+    /// return the next non-empty line and its line number
+    fn next_line(&mut self) -> Option<Result<(usize, &str), Error>> {
+        loop {
+            self.buf.clear();
+            self.line_number += 1;
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if !self.buf.trim().is_empty() {
+                        return Some(Ok((self.line_number, self.buf.trim())));
+                    }
+                }
+                Err(source) => {
+                    return Some(Err(Error::TraceIo {
+                        line: self.line_number,
+                        source,
+                    }))
+                }
+            }
+        }
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+/// open a trace file, decompress it if it's compressed by gzip, zstd or xz
+pub fn open_trace(path: &str) -> Result<Box<dyn BufRead>, Error> {
+    let io_error = |source| Error::Io {
+        path: path.to_string(),
+        source,
+    };
+    let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+    let magic = reader.fill_buf().map_err(io_error)?;
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::Decoder::with_buffer(reader).map_err(io_error)?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if magic.starts_with(XZ_MAGIC) {
+        Ok(Box::new(BufReader::new(
+            xz2::bufread::XzDecoder::new_multi_decoder(reader),
+        )))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// create a trace file, compress it by gzip or zstd if the path ends with `.gz` or `.zst`
+pub fn create_trace(path: &str) -> Result<Box<dyn Write>, Error> {
+    let io_error = |source| Error::Io {
+        path: path.to_string(),
+        source,
+    };
+    let file = BufWriter::new(File::create(path).map_err(io_error)?);
+    if path.ends_with(".gz") {
+        Ok(Box::new(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        )))
+    } else if path.ends_with(".zst") {
+        let encoder = zstd::Encoder::new(file, 0).map_err(io_error)?;
+        Ok(Box::new(encoder.auto_finish()))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> Error {
+    Error::Trace {
+        line,
+        message: message.into(),
+    }
+}
+
+/// parse an addr in hex, the `0x` prefix is optional
+fn parse_hex_addr(text: &str, line: usize) -> Result<u64, Error> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u64::from_str_radix(digits, 16)
+        .map_err(|e| parse_error(line, format!("invalid addr `{}`: {}", text, e)))
+}
+
+/// parse an addr in hex with `0x`, or in decimal
+fn parse_addr(text: &str, line: usize) -> Result<u64, Error> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u64::from_str_radix(digits, 16),
+        None => text.parse(),
+    }
+    .map_err(|e| parse_error(line, format!("invalid addr `{}`: {}", text, e)))
+}
+
+/// the reader of the ramulator memory trace, yield a request per line
+pub struct MemoryTraceReader<R> {
+    lines: TraceLines<R>,
+}
+impl<R: BufRead> MemoryTraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: TraceLines::new(reader),
+        }
+    }
+}
+impl MemoryTraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self::new(open_trace(path)?))
+    }
+}
+impl<R: BufRead> Iterator for MemoryTraceReader<R> {
+    type Item = Result<Request, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, text) = match self.lines.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(parse_memory_line(text, line))
+    }
+}
+fn parse_memory_line(text: &str, line: usize) -> Result<Request, Error> {
+    let mut fields = text.split_whitespace();
+    let addr = parse_hex_addr(fields.next().unwrap(), line)?;
+    let req_type = match fields.next() {
+        Some("R" | "READ") => ReqType::Read,
+        Some("W" | "WRITE") => ReqType::Write,
+        Some(other) => {
+            return Err(parse_error(
+                line,
+                format!("invalid request type `{}`, expect `R` or `W`", other),
+            ))
+        }
+        None => return Err(parse_error(line, "missing the request type")),
+    };
+    if let Some(extra) = fields.next() {
+        return Err(parse_error(line, format!("unexpected field `{}`", extra)));
+    }
+    Ok(Request::new(addr, req_type))
+}
+
+/// the reader of the timed trace, yield a record with the timestamp per line
+pub struct TimedTraceReader<R> {
+    lines: TraceLines<R>,
+}
+impl<R: BufRead> TimedTraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: TraceLines::new(reader),
+        }
+    }
+}
+impl TimedTraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self::new(open_trace(path)?))
+    }
+}
+impl<R: BufRead> Iterator for TimedTraceReader<R> {
+    type Item = Result<TraceRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, text) = match self.lines.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(parse_timed_line(text, line))
+    }
+}
+fn parse_timed_line(text: &str, line: usize) -> Result<TraceRecord, Error> {
+    let (timestamp, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let timestamp = timestamp
+        .parse()
+        .map_err(|e| parse_error(line, format!("invalid timestamp `{}`: {}", timestamp, e)))?;
+    if rest.trim().is_empty() {
+        return Err(parse_error(line, "missing the addr"));
+    }
+    let req = parse_memory_line(rest, line)?;
+    Ok(TraceRecord {
+        timestamp: Some(timestamp),
+        ..TraceRecord::from_request(&req)
+    })
+}
+
+/// the reader of the DRAMSim3 trace, yield a record with the cycle as the timestamp per line
+pub struct DramSim3TraceReader<R> {
+    lines: TraceLines<R>,
+}
+impl<R: BufRead> DramSim3TraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: TraceLines::new(reader),
+        }
+    }
+}
+impl DramSim3TraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self::new(open_trace(path)?))
+    }
+}
+impl<R: BufRead> Iterator for DramSim3TraceReader<R> {
+    type Item = Result<TraceRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, text) = match self.lines.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(parse_dramsim3_line(text, line))
+    }
+}
+fn parse_dramsim3_line(text: &str, line: usize) -> Result<TraceRecord, Error> {
+    let (rest, cycle) = text.rsplit_once(char::is_whitespace).unwrap_or(("", text));
+    let cycle = cycle
+        .parse()
+        .map_err(|e| parse_error(line, format!("invalid cycle `{}`: {}", cycle, e)))?;
+    if rest.trim().is_empty() {
+        return Err(parse_error(line, "missing the addr and the type"));
+    }
+    let req = parse_memory_line(rest, line)?;
+    Ok(TraceRecord {
+        timestamp: Some(cycle),
+        ..TraceRecord::from_request(&req)
+    })
+}
+
+/// the reader of the ramulator cpu trace, yield an entry per line
+pub struct CpuTraceReader<R> {
+    lines: TraceLines<R>,
+}
+impl<R: BufRead> CpuTraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: TraceLines::new(reader),
+        }
+    }
+}
+impl CpuTraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self::new(open_trace(path)?))
+    }
+}
+impl<R: BufRead> Iterator for CpuTraceReader<R> {
+    type Item = Result<CpuTraceEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, text) = match self.lines.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(parse_cpu_line(text, line))
+    }
+}
+fn parse_cpu_line(text: &str, line: usize) -> Result<CpuTraceEntry, Error> {
+    let mut fields = text.split_whitespace();
+    let bubbles = fields.next().unwrap();
+    let bubbles = bubbles
+        .parse()
+        .map_err(|e| parse_error(line, format!("invalid bubble count `{}`: {}", bubbles, e)))?;
+    let read = match fields.next() {
+        Some(addr) => Request::new_read(parse_addr(addr, line)?),
+        None => return Err(parse_error(line, "missing the read addr")),
+    };
+    let writeback = match fields.next() {
+        Some(addr) => Some(Request::new_write(parse_addr(addr, line)?)),
+        None => None,
+    };
+    if let Some(extra) = fields.next() {
+        return Err(parse_error(line, format!("unexpected field `{}`", extra)));
+    }
+    Ok(CpuTraceEntry {
+        bubbles,
+        read,
+        writeback,
+    })
+}
+
+/// a record of the binary trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub addr: u64,
+    pub req_type: ReqType,
+    pub timestamp: Option<u64>,
+}
+impl TraceRecord {
+    pub fn from_request(req: &Request) -> Self {
+        Self {
+            addr: req.addr,
+            req_type: req.req_type,
+            timestamp: None,
+        }
+    }
+    pub fn into_request(self) -> Request {
+        Request::new(self.addr, self.req_type)
+    }
+}
+
+/// the magic bytes at the start of the binary trace, followed by a byte of flags
+const BINARY_MAGIC: &[u8; 8] = b"RAMUTRC1";
+/// the flag of the records carrying a timestamp
+const FLAG_TIMESTAMP: u8 = 1;
+
+/// fill the buf with a record, return false at the end of the trace
+fn read_record(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, String> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(format!("truncated record of {} bytes", read)),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(true)
+}
+
+/// the writer of the binary trace, the layout is:
+/// - header: `RAMUTRC1` and a byte of flags, bit 0 means the records have timestamps
+/// - record: the addr in u64, the type in u8(0 for read, 1 for write), and the timestamp in u64
+///   if the flag is set, all little endian
+pub struct BinaryTraceWriter<W: Write> {
+    writer: W,
+    with_timestamp: bool,
+}
+impl<W: Write> BinaryTraceWriter<W> {
+    pub fn new(mut writer: W, with_timestamp: bool) -> Result<Self, Error> {
+        writer
+            .write_all(BINARY_MAGIC)
+            .and_then(|_| writer.write_all(&[if with_timestamp { FLAG_TIMESTAMP } else { 0 }]))
+            .map_err(Error::TraceWrite)?;
+        Ok(Self {
+            writer,
+            with_timestamp,
+        })
+    }
+    /// write a record, the timestamp is ignored if the trace has no timestamps
+    pub fn write(&mut self, record: &TraceRecord) -> Result<(), Error> {
+        let mut buf = [0; 17];
+        buf[..8].copy_from_slice(&record.addr.to_le_bytes());
+        buf[8] = match record.req_type {
+            ReqType::Read => 0,
+            ReqType::Write => 1,
+        };
+        let len = if self.with_timestamp {
+            buf[9..].copy_from_slice(&record.timestamp.unwrap_or(0).to_le_bytes());
+            17
+        } else {
+            9
+        };
+        self.writer
+            .write_all(&buf[..len])
+            .map_err(Error::TraceWrite)
+    }
+    /// flush the trace and return the inner writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer.flush().map_err(Error::TraceWrite)?;
+        Ok(self.writer)
+    }
+}
+
+/// the reader of the binary trace written by [`BinaryTraceWriter`]
+pub struct BinaryTraceReader<R> {
+    reader: R,
+    with_timestamp: bool,
+    record_number: u64,
+}
+impl<R: Read> BinaryTraceReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0; 9];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| Error::BinaryTrace {
+                record: 0,
+                message: format!("failed to read the header: {}", e),
+            })?;
+        if &header[..8] != BINARY_MAGIC {
+            return Err(Error::BinaryTrace {
+                record: 0,
+                message: "not a binary trace".to_string(),
+            });
+        }
+        Ok(Self {
+            reader,
+            with_timestamp: header[8] & FLAG_TIMESTAMP != 0,
+            record_number: 0,
+        })
+    }
+    /// the records carry timestamps
+    pub fn with_timestamp(&self) -> bool {
+        self.with_timestamp
+    }
+    fn read_record(&mut self) -> Result<Option<TraceRecord>, Error> {
+        self.record_number += 1;
+        let record = self.record_number;
+        let error = |message: String| Error::BinaryTrace { record, message };
+        let len = if self.with_timestamp { 17 } else { 9 };
+        let mut buf = [0; 17];
+        if !read_record(&mut self.reader, &mut buf[..len]).map_err(error)? {
+            return Ok(None);
+        }
+        let req_type = match buf[8] {
+            0 => ReqType::Read,
+            1 => ReqType::Write,
+            other => return Err(error(format!("invalid request type {}", other))),
+        };
+        Ok(Some(TraceRecord {
+            addr: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            req_type,
+            timestamp: self
+                .with_timestamp
+                .then(|| u64::from_le_bytes(buf[9..].try_into().unwrap())),
+        }))
+    }
+}
+impl BinaryTraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Self::new(open_trace(path)?)
+    }
+}
+impl<R: Read> Iterator for BinaryTraceReader<R> {
+    type Item = Result<TraceRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// the memory operands of an instruction in the ChampSim trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChampSimInstr {
+    pub ip: u64,
+    /// the addrs loaded by the instruction
+    pub source_memory: Vec<u64>,
+    /// the addrs stored by the instruction
+    pub destination_memory: Vec<u64>,
+}
+
+/// the bytes of an `input_instr` in the ChampSim trace:
+/// ip(8), is_branch(1), branch_taken(1), destination_registers(2), source_registers(4),
+/// destination_memory(2 * 8), source_memory(4 * 8), all little endian
+const CHAMPSIM_INSTR_SIZE: usize = 64;
+const CHAMPSIM_DESTINATION_MEMORY: usize = 16;
+const CHAMPSIM_SOURCE_MEMORY: usize = 32;
+
+/// the reader of the ChampSim trace, yield an instruction per record
+pub struct ChampSimTraceReader<R> {
+    reader: R,
+    record_number: u64,
+}
+impl<R: Read> ChampSimTraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            record_number: 0,
+        }
+    }
+}
+impl ChampSimTraceReader<Box<dyn BufRead>> {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        Ok(Self::new(open_trace(path)?))
+    }
+}
+impl<R: Read> Iterator for ChampSimTraceReader<R> {
+    type Item = Result<ChampSimInstr, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.record_number += 1;
+        let mut buf = [0; CHAMPSIM_INSTR_SIZE];
+        match read_record(&mut self.reader, &mut buf) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(message) => {
+                return Some(Err(Error::BinaryTrace {
+                    record: self.record_number,
+                    message,
+                }))
+            }
+        }
+        let addrs = |start: usize, count: usize| {
+            buf[start..start + count * 8]
+                .chunks_exact(8)
+                .map(|addr| u64::from_le_bytes(addr.try_into().unwrap()))
+                .filter(|addr| *addr != 0)
+                .collect()
+        };
+        Some(Ok(ChampSimInstr {
+            ip: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            destination_memory: addrs(CHAMPSIM_DESTINATION_MEMORY, 2),
+            source_memory: addrs(CHAMPSIM_SOURCE_MEMORY, 4),
+        }))
+    }
+}
+
+/// the records of the memory operands in the ChampSim trace, the loads and then the stores
+pub fn champsim_records<R: Read>(
+    reader: ChampSimTraceReader<R>,
+) -> impl Iterator<Item = Result<TraceRecord, Error>> {
+    reader.flat_map(|instr| {
+        let records: Vec<_> = match instr {
+            Ok(instr) => {
+                let loads = instr.source_memory.iter().map(|addr| (addr, ReqType::Read));
+                let stores = instr
+                    .destination_memory
+                    .iter()
+                    .map(|addr| (addr, ReqType::Write));
+                loads
+                    .chain(stores)
+                    .map(|(addr, req_type)| {
+                        Ok(TraceRecord {
+                            addr: *addr,
+                            req_type,
+                            timestamp: None,
+                        })
+                    })
+                    .collect()
+            }
+            Err(e) => vec![Err(e)],
+        };
+        records
+    })
+}
+
+/// write all the records to the binary trace, return the number of records
+pub fn convert_to_binary<W: Write>(
+    records: impl IntoIterator<Item = Result<TraceRecord, Error>>,
+    writer: &mut BinaryTraceWriter<W>,
+) -> Result<u64, Error> {
+    let mut count = 0;
+    for record in records {
+        writer.write(&record?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// the records of the cpu trace, the read and then the writeback, the bubbles are dropped
+pub fn cpu_trace_records<R: BufRead>(
+    reader: CpuTraceReader<R>,
+) -> impl Iterator<Item = Result<TraceRecord, Error>> {
+    reader.flat_map(|entry| {
+        let records: Vec<_> = match entry {
+            Ok(entry) => std::iter::once(&entry.read)
+                .chain(entry.writeback.as_ref())
+                .map(|req| Ok(TraceRecord::from_request(req)))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        records
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("ramu_rs_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_memory_trace() {
+        let trace = "0x12345680 R\n\n12345700 W\n0x40 READ\n";
+        let reqs = MemoryTraceReader::new(trace.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(reqs.len(), 3);
+        assert_eq!(reqs[0].addr, 0x12345680);
+        assert!(reqs[0].req_type.is_read());
+        assert_eq!(reqs[1].addr, 0x12345700);
+        assert!(reqs[1].req_type.is_write());
+        assert_eq!(reqs[2].addr, 0x40);
+
+        let trace = "0x40 R\n\n0x80 X\n";
+        let err = MemoryTraceReader::new(trace.as_bytes())
+            .find_map(|req| req.err())
+            .unwrap();
+        assert!(matches!(err, Error::Trace { line: 3, .. }), "{}", err);
+        let err = MemoryTraceReader::new("0xgg R".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, Error::Trace { line: 1, .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_timed_trace() {
+        let trace = "10 0x40 R\n25 0x80 W\n";
+        let records = TimedTraceReader::new(trace.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            records[1],
+            TraceRecord {
+                addr: 0x80,
+                req_type: ReqType::Write,
+                timestamp: Some(25),
+            }
+        );
+        for trace in ["10 0x40 R\n0x80 W\n", "10 0x40 R\n20\n"] {
+            let err = TimedTraceReader::new(trace.as_bytes())
+                .find_map(|record| record.err())
+                .unwrap();
+            assert!(matches!(err, Error::Trace { line: 2, .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_dramsim3_trace() {
+        let trace = "0x40 READ 10\n0x80 WRITE 25\n";
+        let records = DramSim3TraceReader::new(trace.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            records[1],
+            TraceRecord {
+                addr: 0x80,
+                req_type: ReqType::Write,
+                timestamp: Some(25),
+            }
+        );
+        for trace in [
+            "0x40 READ 10\n0x80 WRITE\n",
+            "0x40 READ 10\n0x80 FETCH 20\n",
+        ] {
+            let err = DramSim3TraceReader::new(trace.as_bytes())
+                .find_map(|record| record.err())
+                .unwrap();
+            assert!(matches!(err, Error::Trace { line: 2, .. }), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_champsim_trace() {
+        let mut trace = vec![];
+        for (ip, destination, sources) in [
+            (0x400000u64, [0u64, 0], [0x1000u64, 0x2000, 0, 0]),
+            (0x400004, [0x3000, 0], [0, 0, 0, 0]),
+            (0x400008, [0, 0], [0, 0, 0, 0]),
+        ] {
+            trace.extend(ip.to_le_bytes());
+            trace.extend([0; 8]);
+            for addr in destination.iter().chain(sources.iter()) {
+                trace.extend(addr.to_le_bytes());
+            }
+        }
+        let path = temp_path("trace.champsimtrace.xz");
+        let mut encoder = xz2::write::XzEncoder::new(File::create(&path).unwrap(), 6);
+        encoder.write_all(&trace).unwrap();
+        encoder.finish().unwrap();
+        let instrs = ChampSimTraceReader::from_path(&path)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(instrs.len(), 3);
+        assert_eq!(instrs[0].source_memory, [0x1000, 0x2000]);
+        assert_eq!(instrs[1].destination_memory, [0x3000]);
+        let records = champsim_records(ChampSimTraceReader::from_path(&path).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.addr, r.req_type))
+                .collect::<Vec<_>>(),
+            [
+                (0x1000, ReqType::Read),
+                (0x2000, ReqType::Read),
+                (0x3000, ReqType::Write)
+            ]
+        );
+
+        let err = ChampSimTraceReader::new(&trace[..100])
+            .find_map(|instr| instr.err())
+            .unwrap();
+        assert!(
+            matches!(err, Error::BinaryTrace { record: 2, .. }),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_cpu_trace() {
+        let trace = "3 20734016\n1 0x13c4000 0x4d5e40\n";
+        let entries = CpuTraceReader::new(trace.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries[0].bubbles, 3);
+        assert_eq!(entries[0].read.addr, 20734016);
+        assert!(entries[0].writeback.is_none());
+        assert_eq!(entries[1].read.addr, 0x13c4000);
+        let writeback = entries[1].writeback.as_ref().unwrap();
+        assert_eq!(writeback.addr, 0x4d5e40);
+        assert!(writeback.req_type.is_write());
+
+        for (trace, line) in [("1 0x40\nx 0x80\n", 2), ("1\n", 1), ("1 2 3 4\n", 1)] {
+            let err = CpuTraceReader::new(trace.as_bytes())
+                .find_map(|entry| entry.err())
+                .unwrap();
+            assert!(
+                matches!(err, Error::Trace { line: l, .. } if l == line),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_compressed_trace() {
+        let trace = "0x40 R\n0x80 W\n";
+        for name in ["trace.txt", "trace.txt.gz", "trace.txt.zst"] {
+            let path = temp_path(name);
+            create_trace(&path)
+                .unwrap()
+                .write_all(trace.as_bytes())
+                .unwrap();
+            let reqs = MemoryTraceReader::from_path(&path)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(reqs.len(), 2, "{}", name);
+            assert_eq!(reqs[1].addr, 0x80);
+        }
+    }
+
+    #[test]
+    fn test_binary_trace() {
+        let path = temp_path("trace.bin.zst");
+        let mut writer = BinaryTraceWriter::new(create_trace(&path).unwrap(), false).unwrap();
+        let trace = "1 0x40 0x1000\n2 0x80\n";
+        let count = convert_to_binary(
+            cpu_trace_records(CpuTraceReader::new(trace.as_bytes())),
+            &mut writer,
+        )
+        .unwrap();
+        assert_eq!(count, 3);
+        drop(writer.finish().unwrap());
+        let reader = BinaryTraceReader::from_path(&path).unwrap();
+        assert!(!reader.with_timestamp());
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            records.iter().map(|r| r.addr).collect::<Vec<_>>(),
+            [0x40, 0x1000, 0x80]
+        );
+        assert!(records[1].req_type.is_write());
+
+        let mut writer = BinaryTraceWriter::new(vec![], true).unwrap();
+        let record = TraceRecord {
+            addr: 0x40,
+            req_type: ReqType::Write,
+            timestamp: Some(100),
+        };
+        writer.write(&record).unwrap();
+        let mut buf = writer.finish().unwrap();
+        let records = BinaryTraceReader::new(buf.as_slice())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records, [record]);
+        buf.pop();
+        let err = BinaryTraceReader::new(buf.as_slice())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::BinaryTrace { record: 1, .. }),
+            "{}",
+            err
+        );
+        assert!(BinaryTraceReader::new(&b"0x40 R\n0x80 W\n"[..]).is_err());
+    }
+}
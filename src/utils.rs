@@ -2,12 +2,20 @@
 
 /// truncate lower bits of addr and return the truncated addr
 pub fn slicing_lower_bits(addr: &mut u64, bits: usize) -> u64 {
-    let mask = (1 << bits) - 1;
-    let lower_bits = *addr & mask;
-    *addr >>= bits;
+    let lower_bits = *addr & lower_bits_mask(bits);
+    *addr = addr.checked_shr(bits as u32).unwrap_or(0);
     lower_bits
 }
 
+/// the mask of the lower `bits` bits, all the bits when `bits` is 64 or more
+pub fn lower_bits_mask(bits: usize) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
 /// ignore the lower bits of addr
 pub fn clear_lower_bits(addr: &mut u64, bits: usize) {
     *addr >>= bits;
@@ -37,9 +45,48 @@ pub fn set_up_addr(addr_vec: &[u64], addr_bits: &[usize], sequence: &[usize]) ->
     addr
 }
 
+/// map the addr into addrvec according to bit segments
+/// - each segment `(level, bits)` slices the lower `bits` of addr into the next higher bits of that level
+/// - the segments are ordered from the least significant bit to the most significant bit
+pub fn setup_addr_vec_by_segments(
+    mut addr: u64,
+    addr_vec: &mut [u64],
+    segments: &[(usize, usize)],
+) {
+    addr_vec.fill(0);
+    let mut filled_bits = vec![0; addr_vec.len()];
+    for &(level, bits) in segments.iter() {
+        addr_vec[level] |= slicing_lower_bits(&mut addr, bits)
+            .checked_shl(filled_bits[level] as u32)
+            .unwrap_or(0);
+        filled_bits[level] += bits;
+    }
+}
+
+/// the inverse of `setup_addr_vec_by_segments`
+pub fn set_up_addr_by_segments(addr_vec: &[u64], segments: &[(usize, usize)]) -> u64 {
+    let mut remaining_bits = vec![0; addr_vec.len()];
+    for &(level, bits) in segments.iter() {
+        remaining_bits[level] += bits;
+    }
+    let mut addr = 0u64;
+    for &(level, bits) in segments.iter().rev() {
+        remaining_bits[level] -= bits;
+        addr = addr.checked_shl(bits as u32).unwrap_or(0);
+        addr |= addr_vec[level]
+            .checked_shr(remaining_bits[level] as u32)
+            .unwrap_or(0)
+            & lower_bits_mask(bits);
+    }
+    addr
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::utils::{clear_lower_bits, set_up_addr, setup_addr_vec};
+    use crate::utils::{
+        clear_lower_bits, set_up_addr, set_up_addr_by_segments, setup_addr_vec,
+        setup_addr_vec_by_segments,
+    };
 
     #[test]
     fn test_addr() {
@@ -54,4 +101,30 @@ mod tests {
         let ret_addr = set_up_addr(&addr_vec, &addr_bits, &sequence);
         println!("{:?}", ret_addr);
     }
+
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn test_addr_by_segments() {
+        // level 1 is split into two segments around level 0
+        let segments = [(1, 2), (0, 3), (1, 2)];
+        let mut addr_vec = vec![0, 0];
+        setup_addr_vec_by_segments(0b11_010_01, &mut addr_vec, &segments);
+        assert_eq!(addr_vec, vec![0b010, 0b1101]);
+        assert_eq!(set_up_addr_by_segments(&addr_vec, &segments), 0b11_010_01);
+    }
+
+    #[test]
+    fn test_addr_by_segments_64_bits() {
+        // a single segment takes the whole addr
+        let segments = [(0, 64)];
+        let mut addr_vec = [0; 2];
+        setup_addr_vec_by_segments(u64::MAX - 1, &mut addr_vec, &segments);
+        assert_eq!(addr_vec, [u64::MAX - 1, 0]);
+        assert_eq!(set_up_addr_by_segments(&addr_vec, &segments), u64::MAX - 1);
+
+        let segments = [(1, 4), (0, 60)];
+        setup_addr_vec_by_segments(u64::MAX, &mut addr_vec, &segments);
+        assert_eq!(addr_vec, [(1 << 60) - 1, 0xf]);
+        assert_eq!(set_up_addr_by_segments(&addr_vec, &segments), u64::MAX);
+    }
 }
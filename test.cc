@@ -1,17 +1,36 @@
-
 #include <iostream>
+#include <ramu_rs_callback.h>
 #include <ramu_rs_cpp.h>
 int main() {
   init_logger();
-  auto memory = new_ddr4("ddr4config.toml");
-  memory->try_send_addr(10, false);
+  try {
+    new_memory("not_exist.toml");
+  } catch (const std::exception &e) {
+    std::cout << "error: " << e.what() << std::endl;
+  }
+  auto memory = new_memory("ddr4config.toml");
+  memory->try_send_addr(10, false, 7, 42);
   for (auto i = 0; i < 100; i++) {
-    memory->tick_ddr4();
+    memory->tick();
   }
   uint64_t ret = 0;
   auto write = false;
-  auto is_ret = memory->try_recv_addr(ret, write);
+  uint64_t id = 0;
+  uint64_t tag = 0;
+  auto is_ret = memory->try_recv_addr(ret, write, id, tag);
   if (is_ret) {
-    std::cout << "ret: " << ret << " write: " << write << std::endl;
+    std::cout << "ret: " << ret << " write: " << write << " id: " << id
+              << " tag: " << tag << std::endl;
+  }
+
+  set_callback(*memory, [](uint64_t addr, bool is_write, uint64_t id,
+                           uint64_t tag) {
+    std::cout << "callback: " << addr << " write: " << is_write
+              << " id: " << id << " tag: " << tag << std::endl;
+  });
+  memory->try_send_addr(20, true, 8, 43);
+  for (auto i = 0; i < 100; i++) {
+    memory->tick();
   }
-}
\ No newline at end of file
+  clear_callback(*memory);
+}
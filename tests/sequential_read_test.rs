@@ -1,25 +1,27 @@
-use std::{fs::File, io::BufRead};
-
 use ramu_rs::{
     config::Config,
     ddr4::DDR4,
     memory::{MemoryTrait, SimpleMemory},
     request::{ReqType, Request},
+    trace::CpuTraceReader,
 };
 
 #[test]
 fn sequence_read_test() {
     let config = Config::default();
-    let ddr4 = DDR4::new(&config);
+    let ddr4 = DDR4::new(&config).unwrap();
     let mut memory = SimpleMemory::new(config, ddr4);
     let mut on_going = 0;
     let mut addr_start = 0;
     loop {
-        if let Ok(_) = memory.try_send(Request::new(addr_start, ReqType::Read)) {
+        if memory
+            .try_send(Request::new(addr_start, ReqType::Read))
+            .is_ok()
+        {
             addr_start += 1;
             on_going += 1;
         }
-        if let Some(_) = memory.try_recv() {
+        if memory.try_recv().is_some() {
             on_going -= 1;
         }
         memory.tick();
@@ -28,7 +30,7 @@ fn sequence_read_test() {
         }
     }
     while on_going != 0 {
-        if let Some(_) = memory.try_recv() {
+        if memory.try_recv().is_some() {
             on_going -= 1;
         }
         memory.tick();
@@ -46,23 +48,26 @@ fn sequence_read_test() {
 #[test]
 fn test_read_and_write() {
     let config = Config::default();
-    let ddr4 = DDR4::new(&config);
+    let ddr4 = DDR4::new(&config).unwrap();
     let mut memory = SimpleMemory::new(config, ddr4);
     let mut on_going = 0;
     let mut addr_start = 0;
     loop {
-        if let Ok(_) = memory.try_send(Request::new(
-            addr_start,
-            if addr_start % 2 == 0 {
-                ReqType::Read
-            } else {
-                ReqType::Write
-            },
-        )) {
+        if memory
+            .try_send(Request::new(
+                addr_start,
+                if addr_start % 2 == 0 {
+                    ReqType::Read
+                } else {
+                    ReqType::Write
+                },
+            ))
+            .is_ok()
+        {
             addr_start += 1;
             on_going += 1;
         }
-        if let Some(_) = memory.try_recv() {
+        if memory.try_recv().is_some() {
             on_going -= 1;
         }
         memory.tick();
@@ -71,7 +76,7 @@ fn test_read_and_write() {
         }
     }
     while on_going != 0 {
-        if let Some(_) = memory.try_recv() {
+        if memory.try_recv().is_some() {
             on_going -= 1;
         }
         memory.tick();
@@ -87,22 +92,20 @@ fn test_read_and_write() {
 }
 
 #[test]
+#[ignore = "needs a trace.bin in the working directory"]
 fn test_real_trace() {
-    let file_name = "trace.bin";
-    let file = File::open(file_name).unwrap();
-    let reader = std::io::BufReader::new(file);
-    let num_lines = reader.lines().into_iter().map(|line| line.unwrap());
+    let entries = CpuTraceReader::from_path("trace.bin").unwrap();
     let config = Config::default();
-    let ddr4 = DDR4::new(&config);
+    let ddr4 = DDR4::new(&config).unwrap();
     let mut memory = SimpleMemory::new(config, ddr4);
     let mut on_going = 0;
     let now = std::time::Instant::now();
-    for line in num_lines {
-        let line = line.split_whitespace().collect::<Vec<_>>();
-        let addr = line[1].parse::<u64>().unwrap();
-        while let Err(_) = memory.try_send(Request::new(addr, ReqType::Read)) {
+    for entry in entries {
+        let mut req = entry.unwrap().read;
+        while let Err(e) = memory.try_send(req) {
+            req = e.into_request();
             memory.tick();
-            if let Some(_) = memory.try_recv() {
+            if memory.try_recv().is_some() {
                 on_going -= 1;
             }
         }
@@ -110,7 +113,7 @@ fn test_real_trace() {
     }
     while on_going != 0 {
         memory.tick();
-        if let Some(_) = memory.try_recv() {
+        if memory.try_recv().is_some() {
             on_going -= 1;
         }
     }